@@ -0,0 +1,4957 @@
+//! Generating and solving mazes, independent of how they are drawn.
+
+#![allow(clippy::needless_return)]
+
+use glam::{vec2, Vec2};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+
+pub const CELL_SIZE: f32 = 20.0;
+pub const WALL_WIDTH: f32 = 2.0;
+/// How often a generator walking through the maze takes the stairs when it
+/// could stay on the same layer.
+const STAIR_CHANCE: f32 = 0.1;
+/// How closely a neighbor has to line up with an arrow key to be walked to,
+/// as the cosine of the angle between them. Just too close for the diagonal
+/// neighbors of a hexagon to the sides.
+const MIN_ALIGNMENT: f32 = 0.6;
+/// How much the length of the solution, the junctions along it and the dead
+/// ends off it add to the difficulty of a maze. They add up to one.
+const LENGTH_WEIGHT: f32 = 0.4;
+const JUNCTION_WEIGHT: f32 = 0.3;
+const DEAD_END_WEIGHT: f32 = 0.3;
+/// Smallest and largest width and height of the rooms placed by `carve_rooms`.
+const MIN_ROOM_SIZE: usize = 2;
+const MAX_ROOM_SIZE: usize = 5;
+/// How often `carve_rooms` tries to find a free spot for each room.
+const ROOM_ATTEMPTS: usize = 100;
+/// What entering a swamp cell placed by `add_terrain` costs, against one for
+/// any other cell.
+pub const SWAMP_WEIGHT: u32 = 5;
+/// Smallest and largest number of cells in a swamp placed by `add_terrain`.
+const MIN_SWAMP_SIZE: usize = 3;
+const MAX_SWAMP_SIZE: usize = 12;
+/// How close to a wall a point has to be for `wall_at` to pick it, as a share
+/// of the way from the wall to the center of the cell.
+const WALL_REACH: f32 = 0.5;
+/// How many steps of the recursive backtracker can be undone.
+const UNDO_DEPTH: usize = 10_000;
+/// The distance to a cell that can't be reached at all.
+pub const UNREACHABLE: u32 = u32::MAX;
+/// How close to opposite two passages have to be to make a straight corridor,
+/// as the cosine of the angle between them. Also how straight a biased
+/// backtracker has to keep carving.
+const STRAIGHT_ALIGNMENT: f32 = 0.9;
+/// The first bytes of every `.maze` file.
+const MAZE_MAGIC: &[u8; 4] = b"MAZE";
+/// The version of the `.maze` format written by `save_maze`.
+const MAZE_VERSION: u8 = 1;
+/// Magic, version, rows and cols.
+const MAZE_HEADER_LEN: usize = 13;
+
+fn index(row: i32, col: i32, rows: i32, cols: i32) -> Option<usize> {
+    if row < 0 || col < 0 || row > rows - 1 || col > cols - 1 {
+        return None;
+    }
+    return Some((row * cols + col) as usize);
+}
+
+/// The shape of the cells and which of them count as neighbors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Topology {
+    #[default]
+    Square,
+    /// Pointy-top hexagons in axial coordinates, so every row is shifted half a
+    /// cell to the right of the one above. Generators that work row by row only
+    /// carve through the square directions, which are still neighbors here.
+    Hex,
+    /// Triangles pointing up and down in turns, starting with an upward one in
+    /// the top left corner. Each has a left and right neighbor and one above or
+    /// below it.
+    Triangle,
+    /// Concentric rings around a single hub cell, so `row` is the ring and
+    /// `col` counts clockwise from the top. `rows` sets the number of rings and
+    /// `cols` is the size of the outermost one. The `left` and `right` walls
+    /// are the counterclockwise and clockwise ones and `top` is the inner one,
+    /// so every outer wall is the `top` wall of a cell in the next ring, except
+    /// in the outermost ring, where it is `bot`.
+    Polar,
+}
+
+impl Topology {
+    /// Directions of the neighbors of the cell at `row` and `col`, clockwise
+    /// from the first one at or after north.
+    pub fn directions(self, row: usize, col: usize) -> &'static [Direction] {
+        use Direction::*;
+        return match self {
+            Topology::Square => &[North, East, South, West],
+            Topology::Hex => &[NorthEast, East, South, SouthWest, West, North],
+            Topology::Triangle if (row + col).is_multiple_of(2) => &[East, South, West],
+            Topology::Triangle => &[North, East, West],
+            // rings don't line up, see `Grid::polar_neighbors`
+            Topology::Polar => &[],
+        };
+    }
+
+    /// The most neighbors a single cell can have.
+    pub fn sides(self) -> usize {
+        return match self {
+            Topology::Square => 4,
+            Topology::Hex => 6,
+            Topology::Triangle => 3,
+            // the hub, next to every cell of the first ring
+            Topology::Polar => 6,
+        };
+    }
+
+    pub fn cycle(self) -> Self {
+        return match self {
+            Topology::Square => Topology::Hex,
+            Topology::Hex => Topology::Triangle,
+            Topology::Triangle => Topology::Polar,
+            Topology::Polar => Topology::Square,
+        };
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Topology::Square => "square",
+            Topology::Hex => "hex",
+            Topology::Triangle => "triangle",
+            Topology::Polar => "polar",
+        };
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        return match name {
+            "square" => Some(Topology::Square),
+            "hex" => Some(Topology::Hex),
+            "triangle" => Some(Topology::Triangle),
+            "polar" => Some(Topology::Polar),
+            _ => None,
+        };
+    }
+
+    /// How many rows and cols of these cells fit into `width` and `height`,
+    /// at least one of each, with room for walls `wall_width` thick around a
+    /// polar grid. Rows come from the height and cols from the width.
+    pub fn fit(self, width: f32, height: f32, wall_width: f32) -> (usize, usize) {
+        let (rows, cols) = match self {
+            Topology::Square => (height / CELL_SIZE, width / CELL_SIZE),
+            Topology::Hex => {
+                // rows overlap by a quarter of a hexagon and each one is shifted
+                // half a hexagon further right
+                let rows = ((height / HEX_RADIUS - 0.5) / 1.5).floor();
+                (rows, width / HEX_WIDTH - (rows - 1.0) / 2.0 - 0.5)
+            }
+            // neighboring triangles overlap by half of their width
+            Topology::Triangle => (
+                height / TRIANGLE_HEIGHT,
+                width / (TRIANGLE_SIDE / 2.0) - 1.0,
+            ),
+            Topology::Polar => ((width.min(height) / 2.0 - wall_width) / RING_WIDTH, 1.0),
+        };
+        return (
+            (rows.floor() as usize).max(1),
+            (cols.floor().max(0.0) as usize).max(1),
+        );
+    }
+}
+
+/// Distance between the centers of two hexagons in the same row.
+pub const HEX_WIDTH: f32 = CELL_SIZE;
+/// Distance from the center of a hexagon to its corners.
+pub const HEX_RADIUS: f32 = HEX_WIDTH / 1.732_050_8;
+
+/// Length of the sides of a triangle.
+pub const TRIANGLE_SIDE: f32 = 1.5 * CELL_SIZE;
+pub const TRIANGLE_HEIGHT: f32 = TRIANGLE_SIDE * 0.866_025_4;
+
+/// Width of the rings of a polar grid, and the radius of its hub.
+pub const RING_WIDTH: f32 = CELL_SIZE;
+
+/// Number of cells in each of the first `rings` rings of a polar grid. A ring
+/// has one or more cells for every cell of the ring inside it, so that each
+/// cell has a single inner neighbor.
+pub fn ring_sizes(rings: usize) -> Vec<usize> {
+    let mut sizes: Vec<usize> = Vec::new();
+    for ring in 0..rings {
+        if ring == 0 {
+            sizes.push(1);
+            continue;
+        }
+        // keep the cells about as wide as the rings
+        let inner = sizes[ring - 1];
+        let split = (std::f32::consts::TAU * ring as f32 / inner as f32).round();
+        sizes.push(inner * (split as usize).max(1));
+    }
+    return sizes;
+}
+
+/// The point `radius` away from `origin` in the direction of `angle`.
+pub fn polar(origin: Vec2, radius: f32, angle: f32) -> Vec2 {
+    return origin + radius * vec2(angle.cos(), angle.sin());
+}
+
+/// Enough straight pieces for an arc of `radius` between the angles `from` and
+/// `to` to look round.
+pub fn arc_segments(radius: f32, from: f32, to: f32) -> usize {
+    return ((to - from) * radius / 4.0).ceil().max(1.0) as usize;
+}
+
+/// The straight pieces of an arc of `radius` between the angles `from` and `to`.
+pub fn arc(origin: Vec2, radius: f32, from: f32, to: f32) -> Vec<(Vec2, Vec2)> {
+    let segments = arc_segments(radius, from, to);
+    let step = (to - from) / segments as f32;
+    return (0..segments)
+        .map(|i| {
+            return (
+                polar(origin, radius, from + step * i as f32),
+                polar(origin, radius, from + step * (i + 1) as f32),
+            );
+        })
+        .collect();
+}
+
+/// The most neighbors a cell can have: the six around a hexagon or the hub of
+/// a polar grid, and the stairs up and down.
+pub const MAX_NEIGHBORS: usize = 8;
+
+/// Indices of the neighbors of a cell, kept on the stack since generators and
+/// solvers look them up on every step.
+#[derive(Debug, Clone, Copy)]
+pub struct Neighbors {
+    cells: [usize; MAX_NEIGHBORS],
+    len: usize,
+}
+
+impl Neighbors {
+    fn new() -> Self {
+        return Self {
+            cells: [0; MAX_NEIGHBORS],
+            len: 0,
+        };
+    }
+
+    fn push(&mut self, index: usize) {
+        self.cells[self.len] = index;
+        self.len += 1;
+    }
+
+    fn retain(&mut self, mut keep: impl FnMut(usize) -> bool) {
+        *self = self.into_iter().filter(|&index| keep(index)).collect();
+    }
+}
+
+impl std::ops::Deref for Neighbors {
+    type Target = [usize];
+
+    fn deref(&self) -> &[usize] {
+        return &self.cells[..self.len];
+    }
+}
+
+impl IntoIterator for Neighbors {
+    type Item = usize;
+    type IntoIter = std::iter::Take<std::array::IntoIter<usize, MAX_NEIGHBORS>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        return self.cells.into_iter().take(self.len);
+    }
+}
+
+impl Extend<usize> for Neighbors {
+    fn extend<I: IntoIterator<Item = usize>>(&mut self, indices: I) {
+        for index in indices {
+            self.push(index);
+        }
+    }
+}
+
+impl FromIterator<usize> for Neighbors {
+    fn from_iter<I: IntoIterator<Item = usize>>(indices: I) -> Self {
+        let mut neighbors = Neighbors::new();
+        neighbors.extend(indices);
+        return neighbors;
+    }
+}
+
+/// A side of a cell, and the way to the neighbor behind it. On hex grids
+/// `South` is down and to the right, and `North` up and to the left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    North,
+    East,
+    South,
+    West,
+    /// Only on hex grids, towards `(row - 1, col + 1)`.
+    NorthEast,
+    /// Only on hex grids, towards `(row + 1, col - 1)`.
+    SouthWest,
+}
+
+impl Direction {
+    /// `(row, col)` offset of the neighbor in this direction.
+    pub fn offset(self) -> (i32, i32) {
+        return match self {
+            Direction::North => (-1, 0),
+            Direction::East => (0, 1),
+            Direction::South => (1, 0),
+            Direction::West => (0, -1),
+            Direction::NorthEast => (-1, 1),
+            Direction::SouthWest => (1, -1),
+        };
+    }
+
+    pub fn from_offset(offset: (i32, i32)) -> Option<Self> {
+        return match offset {
+            (-1, 0) => Some(Direction::North),
+            (0, 1) => Some(Direction::East),
+            (1, 0) => Some(Direction::South),
+            (0, -1) => Some(Direction::West),
+            (-1, 1) => Some(Direction::NorthEast),
+            (1, -1) => Some(Direction::SouthWest),
+            _ => None,
+        };
+    }
+
+    /// The side of the neighbor that faces back.
+    pub fn opposite(self) -> Self {
+        return match self {
+            Direction::North => Direction::South,
+            Direction::East => Direction::West,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
+            Direction::NorthEast => Direction::SouthWest,
+            Direction::SouthWest => Direction::NorthEast,
+        };
+    }
+}
+
+fn closed_wall() -> bool {
+    return true;
+}
+
+/// The walls of a cell that are standing, one bit each, like the `bitflags`
+/// crate would make them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Walls(u8);
+
+impl Walls {
+    pub const NONE: Walls = Walls(0);
+    pub const TOP: Walls = Walls(1);
+    pub const RIGHT: Walls = Walls(1 << 1);
+    pub const BOTTOM: Walls = Walls(1 << 2);
+    pub const LEFT: Walls = Walls(1 << 3);
+    /// Only used on hex grids, towards `(row - 1, col + 1)`.
+    pub const TOP_RIGHT: Walls = Walls(1 << 4);
+    /// Only used on hex grids, towards `(row + 1, col - 1)`.
+    pub const BOTTOM_LEFT: Walls = Walls(1 << 5);
+    /// Stairs to the same position on the next layer up.
+    pub const UP: Walls = Walls(1 << 6);
+    pub const DOWN: Walls = Walls(1 << 7);
+    pub const ALL: Walls = Walls(u8::MAX);
+
+    pub fn bits(self) -> u8 {
+        return self.0;
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        return Walls(bits);
+    }
+
+    /// The wall on the side facing `direction`.
+    pub fn side(direction: Direction) -> Self {
+        return match direction {
+            Direction::North => Walls::TOP,
+            Direction::East => Walls::RIGHT,
+            Direction::South => Walls::BOTTOM,
+            Direction::West => Walls::LEFT,
+            Direction::NorthEast => Walls::TOP_RIGHT,
+            Direction::SouthWest => Walls::BOTTOM_LEFT,
+        };
+    }
+
+    /// Whether all of `walls` are standing.
+    pub fn contains(self, walls: Walls) -> bool {
+        return self.0 & walls.0 == walls.0;
+    }
+
+    pub fn set(&mut self, walls: Walls, closed: bool) {
+        if closed {
+            self.0 |= walls.0;
+        } else {
+            self.0 &= !walls.0;
+        }
+    }
+
+    pub fn is_open(self, direction: Direction) -> bool {
+        return !self.contains(Walls::side(direction));
+    }
+
+    pub fn open(&mut self, direction: Direction) {
+        self.set_open(direction, true);
+    }
+
+    pub fn set_open(&mut self, direction: Direction, open: bool) {
+        self.set(Walls::side(direction), !open);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(from = "SavedCell", into = "SavedCell")]
+pub struct Cell {
+    pub layer: usize,
+    pub row: usize,
+    pub col: usize,
+    pub visited: bool,
+    pub filled: bool,
+    pub walls: Walls,
+    /// The passage running under the cell at a crossing of a weave maze, as
+    /// `North` for one from north to south or `East` for one from east to west.
+    pub under: Option<Direction>,
+    /// Left out of a sparse maze, see `Grid::block_cells`.
+    pub blocked: bool,
+    /// Whether the player came close enough to see the cell in fog mode.
+    pub seen: bool,
+}
+
+/// How a `Cell` is saved, with a field for each wall so that files from
+/// before `Walls` still load.
+#[derive(Serialize, Deserialize)]
+struct SavedCell {
+    #[serde(default)]
+    layer: usize,
+    row: usize,
+    col: usize,
+    visited: bool,
+    filled: bool,
+    top: bool,
+    bot: bool,
+    left: bool,
+    right: bool,
+    #[serde(default = "closed_wall")]
+    top_right: bool,
+    #[serde(default = "closed_wall")]
+    bot_left: bool,
+    #[serde(default = "closed_wall")]
+    up: bool,
+    #[serde(default = "closed_wall")]
+    down: bool,
+    #[serde(default)]
+    under: Option<Direction>,
+    #[serde(default)]
+    blocked: bool,
+}
+
+impl From<SavedCell> for Cell {
+    fn from(saved: SavedCell) -> Self {
+        let mut walls = Walls::NONE;
+        walls.set(Walls::TOP, saved.top);
+        walls.set(Walls::BOTTOM, saved.bot);
+        walls.set(Walls::LEFT, saved.left);
+        walls.set(Walls::RIGHT, saved.right);
+        walls.set(Walls::TOP_RIGHT, saved.top_right);
+        walls.set(Walls::BOTTOM_LEFT, saved.bot_left);
+        walls.set(Walls::UP, saved.up);
+        walls.set(Walls::DOWN, saved.down);
+        return Self {
+            layer: saved.layer,
+            row: saved.row,
+            col: saved.col,
+            visited: saved.visited,
+            filled: saved.filled,
+            walls,
+            under: saved.under,
+            blocked: saved.blocked,
+            seen: false,
+        };
+    }
+}
+
+impl From<Cell> for SavedCell {
+    fn from(cell: Cell) -> Self {
+        return Self {
+            layer: cell.layer,
+            row: cell.row,
+            col: cell.col,
+            visited: cell.visited,
+            filled: cell.filled,
+            top: cell.walls.contains(Walls::TOP),
+            bot: cell.walls.contains(Walls::BOTTOM),
+            left: cell.walls.contains(Walls::LEFT),
+            right: cell.walls.contains(Walls::RIGHT),
+            top_right: cell.walls.contains(Walls::TOP_RIGHT),
+            bot_left: cell.walls.contains(Walls::BOTTOM_LEFT),
+            up: cell.walls.contains(Walls::UP),
+            down: cell.walls.contains(Walls::DOWN),
+            under: cell.under,
+            blocked: cell.blocked,
+        };
+    }
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        return Self {
+            layer: 0,
+            row: 0,
+            col: 0,
+            visited: false,
+            filled: false,
+            walls: Walls::ALL,
+            under: None,
+            blocked: false,
+            seen: false,
+        };
+    }
+}
+
+impl Cell {
+    /// Every wall of the cell, whether it's blocked and the passage under it,
+    /// packed into one number.
+    fn wall_bits(&self) -> u16 {
+        // in the order from before `Walls`, so fingerprints stay the same
+        let walls = [
+            Walls::TOP,
+            Walls::BOTTOM,
+            Walls::LEFT,
+            Walls::RIGHT,
+            Walls::TOP_RIGHT,
+            Walls::BOTTOM_LEFT,
+            Walls::UP,
+            Walls::DOWN,
+        ]
+        .map(|wall| self.walls.contains(wall));
+        let bits = walls
+            .iter()
+            .chain([&self.blocked])
+            .enumerate()
+            .fold(0, |bits, (bit, &wall)| bits | (wall as u16) << bit);
+        let under = match self.under {
+            None => 0,
+            Some(Direction::North) => 1,
+            Some(_) => 2,
+        };
+        return bits | under << (walls.len() + 1);
+    }
+
+    /// The middle of the cell, with cells `cell_w` wide and `cell_h` high.
+    pub fn center(&self, (cell_w, cell_h): (f32, f32)) -> Vec2 {
+        return vec2(
+            (self.col as f32 + 0.5) * cell_w,
+            (self.row as f32 + 0.5) * cell_h,
+        );
+    }
+
+    /// The lines of the walls that are in place, see `center`.
+    pub fn walls(&self, (cell_w, cell_h): (f32, f32)) -> Vec<(Vec2, Vec2)> {
+        let (x, y) = (self.col as f32 * cell_w, self.row as f32 * cell_h);
+        let (top_left, top_right) = (vec2(x, y), vec2(x + cell_w, y));
+        let (bot_left, bot_right) = (vec2(x, y + cell_h), vec2(x + cell_w, y + cell_h));
+        let mut walls: Vec<(Vec2, Vec2)> = [
+            (Direction::North, top_left, top_right),
+            (Direction::South, bot_left, bot_right),
+            (Direction::West, top_left, bot_left),
+            (Direction::East, top_right, bot_right),
+        ]
+        .into_iter()
+        .filter(|&(side, _, _)| !self.walls.is_open(side))
+        .map(|(_, from, to)| (from, to))
+        .collect();
+        walls.extend(self.crossing_walls((cell_w, cell_h)));
+        return walls;
+    }
+
+    /// The sides of the passage across a crossing, and the ends of the one
+    /// that runs under it. Empty for any other cell.
+    pub fn crossing_walls(&self, (cell_w, cell_h): (f32, f32)) -> Vec<(Vec2, Vec2)> {
+        let Some(under) = self.under else {
+            return Vec::new();
+        };
+        let (x, y) = (self.col as f32 * cell_w, self.row as f32 * cell_h);
+        let near = vec2(cell_w, cell_h) / 4.0;
+        let far = vec2(cell_w, cell_h) * 3.0 / 4.0;
+        let mut walls = Vec::new();
+        for offset in [near, far] {
+            // one line across the whole cell and two stubs at its edges
+            let (across, stubs) = if under == Direction::North {
+                (
+                    (vec2(x, y + offset.y), vec2(x + cell_w, y + offset.y)),
+                    [
+                        (vec2(x + offset.x, y), vec2(x + offset.x, y + near.y)),
+                        (
+                            vec2(x + offset.x, y + far.y),
+                            vec2(x + offset.x, y + cell_h),
+                        ),
+                    ],
+                )
+            } else {
+                (
+                    (vec2(x + offset.x, y), vec2(x + offset.x, y + cell_h)),
+                    [
+                        (vec2(x, y + offset.y), vec2(x + near.x, y + offset.y)),
+                        (
+                            vec2(x + far.x, y + offset.y),
+                            vec2(x + cell_w, y + offset.y),
+                        ),
+                    ],
+                )
+            };
+            walls.push(across);
+            walls.extend(stubs);
+        }
+        return walls;
+    }
+
+    pub fn hex_center(&self) -> Vec2 {
+        return vec2(
+            (self.col as f32 + self.row as f32 / 2.0 + 0.5) * HEX_WIDTH,
+            (self.row as f32 * 1.5 + 1.0) * HEX_RADIUS,
+        );
+    }
+
+    pub fn hex_walls(&self) -> Vec<(Vec2, Vec2)> {
+        // corners clockwise from the upper right one, so that edge `i` runs
+        // from corner `i` to corner `i + 1`
+        let center = self.hex_center();
+        let corners: Vec<Vec2> = (0..6)
+            .map(|i| {
+                let angle = (60.0 * i as f32 - 30.0).to_radians();
+                return center + HEX_RADIUS * vec2(angle.cos(), angle.sin());
+            })
+            .collect();
+        let sides = [
+            Direction::East,
+            Direction::South,
+            Direction::SouthWest,
+            Direction::West,
+            Direction::North,
+            Direction::NorthEast,
+        ];
+        return (0..6)
+            .filter(|&i| !self.walls.is_open(sides[i]))
+            .map(|i| (corners[i], corners[(i + 1) % 6]))
+            .collect();
+    }
+
+    pub fn points_up(&self) -> bool {
+        return (self.row + self.col).is_multiple_of(2);
+    }
+
+    /// Left, right and then the top or bottom corner.
+    pub fn triangle_corners(&self) -> [Vec2; 3] {
+        let x = self.col as f32 * TRIANGLE_SIDE / 2.0;
+        let (top, bottom) = (
+            self.row as f32 * TRIANGLE_HEIGHT,
+            (self.row + 1) as f32 * TRIANGLE_HEIGHT,
+        );
+        let tip = x + TRIANGLE_SIDE / 2.0;
+        if self.points_up() {
+            return [
+                vec2(x, bottom),
+                vec2(x + TRIANGLE_SIDE, bottom),
+                vec2(tip, top),
+            ];
+        }
+        return [
+            vec2(x, top),
+            vec2(x + TRIANGLE_SIDE, top),
+            vec2(tip, bottom),
+        ];
+    }
+
+    pub fn triangle_center(&self) -> Vec2 {
+        let [a, b, c] = self.triangle_corners();
+        return (a + b + c) / 3.0;
+    }
+
+    pub fn triangle_walls(&self) -> Vec<(Vec2, Vec2)> {
+        let [left, right, tip] = self.triangle_corners();
+        let base = if self.points_up() {
+            Direction::South
+        } else {
+            Direction::North
+        };
+        return [
+            (Direction::West, left, tip),
+            (Direction::East, right, tip),
+            (base, left, right),
+        ]
+        .into_iter()
+        .filter(|&(side, _, _)| !self.walls.is_open(side))
+        .map(|(_, from, to)| (from, to))
+        .collect();
+    }
+
+    /// Inner and outer radius and the angles of the counterclockwise and
+    /// clockwise sides, for a cell in a ring of `size` cells.
+    pub fn polar_bounds(&self, size: usize) -> (f32, f32, f32, f32) {
+        let angle = std::f32::consts::TAU / size as f32;
+        let from = self.col as f32 * angle - std::f32::consts::FRAC_PI_2;
+        return (
+            self.row as f32 * RING_WIDTH,
+            (self.row + 1) as f32 * RING_WIDTH,
+            from,
+            from + angle,
+        );
+    }
+
+    pub fn polar_center(&self, origin: Vec2, size: usize) -> Vec2 {
+        if self.row == 0 {
+            return origin;
+        }
+        let (inner, outer, from, to) = self.polar_bounds(size);
+        return polar(origin, (inner + outer) / 2.0, (from + to) / 2.0);
+    }
+
+    /// `outermost` cells also have their outer wall, which has no cell of the
+    /// next ring to store it.
+    pub fn polar_walls(&self, origin: Vec2, size: usize, outermost: bool) -> Vec<(Vec2, Vec2)> {
+        let (inner, outer, from, to) = self.polar_bounds(size);
+        let mut walls: Vec<(Vec2, Vec2)> = Vec::new();
+        if self.row > 0 {
+            if !self.walls.is_open(Direction::North) {
+                walls.extend(arc(origin, inner, from, to));
+            }
+            for (side, angle) in [(Direction::West, from), (Direction::East, to)] {
+                if !self.walls.is_open(side) {
+                    walls.push((polar(origin, inner, angle), polar(origin, outer, angle)));
+                }
+            }
+        }
+        if outermost && !self.walls.is_open(Direction::South) {
+            walls.extend(arc(origin, outer, from, to));
+        }
+        return walls;
+    }
+}
+
+/// Union-find over cell indices, used to track which cells are already connected.
+#[derive(Serialize, Deserialize)]
+struct DisjointSet {
+    parent: Vec<usize>,
+    rank: Vec<u8>,
+}
+
+impl DisjointSet {
+    fn new(size: usize) -> Self {
+        return Self {
+            parent: (0..size).collect(),
+            rank: vec![0; size],
+        };
+    }
+
+    fn find(&mut self, index: usize) -> usize {
+        let mut root = index;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+
+        // path compression
+        let mut index = index;
+        while self.parent[index] != root {
+            let parent = self.parent[index];
+            self.parent[index] = root;
+            index = parent;
+        }
+        return root;
+    }
+
+    /// Merges the sets of `a` and `b`, returning `false` if they were already joined.
+    fn union(&mut self, a: usize, b: usize) -> bool {
+        let a = self.find(a);
+        let b = self.find(b);
+        if a == b {
+            return false;
+        }
+
+        if self.rank[a] < self.rank[b] {
+            self.parent[a] = b;
+        } else if self.rank[a] > self.rank[b] {
+            self.parent[b] = a;
+        } else {
+            self.parent[b] = a;
+            self.rank[a] += 1;
+        }
+        return true;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GenAlgorithm {
+    RecursiveBacktracker,
+    Prim,
+    Kruskal,
+    Wilson,
+    AldousBroder,
+    Eller,
+    RecursiveDivision,
+    BinaryTree,
+    Sidewinder,
+    HuntAndKill,
+    GrowingTree,
+}
+
+impl GenAlgorithm {
+    pub fn cycle(self) -> Self {
+        return match self {
+            GenAlgorithm::RecursiveBacktracker => GenAlgorithm::Prim,
+            GenAlgorithm::Prim => GenAlgorithm::Kruskal,
+            GenAlgorithm::Kruskal => GenAlgorithm::Wilson,
+            GenAlgorithm::Wilson => GenAlgorithm::AldousBroder,
+            GenAlgorithm::AldousBroder => GenAlgorithm::Eller,
+            GenAlgorithm::Eller => GenAlgorithm::RecursiveDivision,
+            GenAlgorithm::RecursiveDivision => GenAlgorithm::BinaryTree,
+            GenAlgorithm::BinaryTree => GenAlgorithm::Sidewinder,
+            GenAlgorithm::Sidewinder => GenAlgorithm::HuntAndKill,
+            GenAlgorithm::HuntAndKill => GenAlgorithm::GrowingTree,
+            GenAlgorithm::GrowingTree => GenAlgorithm::RecursiveBacktracker,
+        };
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            GenAlgorithm::RecursiveBacktracker => "Recursive Backtracker",
+            GenAlgorithm::Prim => "Prim",
+            GenAlgorithm::Kruskal => "Kruskal",
+            GenAlgorithm::Wilson => "Wilson",
+            GenAlgorithm::AldousBroder => "Aldous-Broder",
+            GenAlgorithm::Eller => "Eller",
+            GenAlgorithm::RecursiveDivision => "Recursive Division",
+            GenAlgorithm::BinaryTree => "Binary Tree",
+            GenAlgorithm::Sidewinder => "Sidewinder",
+            GenAlgorithm::HuntAndKill => "Hunt-and-Kill",
+            GenAlgorithm::GrowingTree => "Growing Tree",
+        };
+    }
+
+    /// The algorithm with the given `name`, ignoring case and with dashes in
+    /// place of spaces, like `recursive-backtracker`.
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.to_lowercase().replace(' ', "-");
+        let mut algorithm = GenAlgorithm::RecursiveBacktracker;
+        loop {
+            if algorithm.name().to_lowercase().replace(' ', "-") == name {
+                return Some(algorithm);
+            }
+            algorithm = algorithm.cycle();
+            if algorithm == GenAlgorithm::RecursiveBacktracker {
+                return None;
+            }
+        }
+    }
+
+    /// Whether the algorithm grows the maze from cell to cell, and so can
+    /// leave blocked cells alone.
+    pub fn grows(self) -> bool {
+        return matches!(
+            self,
+            GenAlgorithm::RecursiveBacktracker
+                | GenAlgorithm::Prim
+                | GenAlgorithm::HuntAndKill
+                | GenAlgorithm::GrowingTree
+        );
+    }
+
+    /// Eller's, recursive division, binary tree and sidewinder carve north and
+    /// east through rows of equal length, which aren't both neighbors of every
+    /// triangle and don't exist on polar grids. They never take the stairs
+    /// either.
+    pub fn supports(self, topology: Topology, layers: usize) -> bool {
+        return match self {
+            GenAlgorithm::Eller
+            | GenAlgorithm::RecursiveDivision
+            | GenAlgorithm::BinaryTree
+            | GenAlgorithm::Sidewinder => {
+                layers == 1 && !matches!(topology, Topology::Triangle | Topology::Polar)
+            }
+            _ => true,
+        };
+    }
+}
+
+/// How the growing tree generator picks the next cell from its active list.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CellPickPolicy {
+    Newest,
+    Random,
+    Oldest,
+    /// Picks the newest cell with the given probability, otherwise a random one.
+    MixNewestRandom(f32),
+}
+
+impl CellPickPolicy {
+    pub fn cycle(self) -> Self {
+        return match self {
+            CellPickPolicy::Newest => CellPickPolicy::Random,
+            CellPickPolicy::Random => CellPickPolicy::Oldest,
+            CellPickPolicy::Oldest => CellPickPolicy::MixNewestRandom(0.5),
+            CellPickPolicy::MixNewestRandom(_) => CellPickPolicy::Newest,
+        };
+    }
+
+    pub fn name(&self) -> String {
+        return match self {
+            CellPickPolicy::Newest => "Newest".to_string(),
+            CellPickPolicy::Random => "Random".to_string(),
+            CellPickPolicy::Oldest => "Oldest".to_string(),
+            CellPickPolicy::MixNewestRandom(newest) => {
+                format!("{:.0}% Newest / Random", newest * 100.0)
+            }
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolveAlgorithm {
+    Bfs,
+    AStar,
+    Dijkstra,
+    DeadEndFilling,
+    WallFollower,
+}
+
+/// The parts of a `Grid` that are saved to JSON.
+#[derive(Serialize, Deserialize)]
+struct SavedGrid {
+    rows: usize,
+    cols: usize,
+    cells: Vec<Cell>,
+    start: usize,
+    goal: usize,
+    #[serde(default)]
+    topology: Topology,
+    #[serde(default = "one_layer")]
+    layers: usize,
+    #[serde(default)]
+    wrap_x: bool,
+    #[serde(default)]
+    wrap_y: bool,
+    #[serde(default)]
+    portals: Vec<(usize, usize)>,
+    /// Empty in files from before terrain, where every cell costs 1.
+    #[serde(default)]
+    weights: Vec<u32>,
+}
+
+/// A maze that is still being generated, with everything the generator needs
+/// to carry on exactly where it stopped.
+#[derive(Serialize, Deserialize)]
+struct SavedState {
+    #[serde(flatten)]
+    grid: SavedGrid,
+    seed: u64,
+    /// Where the random number generator is, not the seed it started from.
+    rng: u64,
+    algorithm: GenAlgorithm,
+    policy: CellPickPolicy,
+    straightness: f32,
+    last_direction: Option<[f32; 2]>,
+    finished: bool,
+    stack: VecDeque<usize>,
+    current: usize,
+    next: Option<usize>,
+    frontier: Vec<usize>,
+    walls: Vec<(usize, usize)>,
+    sets: DisjointSet,
+    walk: Vec<usize>,
+    erased: Vec<usize>,
+    remaining: usize,
+    row_sets: Vec<usize>,
+    next_set: usize,
+    active_row: Option<usize>,
+    regions: Vec<(usize, usize, usize, usize)>,
+    run: Vec<usize>,
+    run_closed: bool,
+    hunt_row: Option<usize>,
+    #[serde(default)]
+    rooms: Vec<(usize, usize, usize, usize)>,
+}
+
+/// What a step of the recursive backtracker changed, to be able to undo it.
+struct UndoStep {
+    current: usize,
+    next: Option<usize>,
+    /// Whether `current` was already visited before the step.
+    visited: bool,
+    /// Whether the step carved a passage to `next`; otherwise it backtracked.
+    carved: bool,
+    last_direction: Option<Vec2>,
+    rng: u64,
+}
+
+fn one_layer() -> usize {
+    return 1;
+}
+
+/// Error returned when reading a maze from text fails.
+#[derive(Debug)]
+pub struct ParseError {
+    line: usize,
+    message: String,
+}
+
+impl ParseError {
+    fn new(line: usize, message: &str) -> Self {
+        return Self {
+            line,
+            message: message.to_string(),
+        };
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        return write!(f, "line {}: {}", self.line, self.message);
+    }
+}
+
+/// Which cells of a square grid may be carved, row by row, for mazes in the
+/// shape of a picture. There is at least one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mask {
+    rows: usize,
+    cols: usize,
+    carveable: Vec<bool>,
+}
+
+impl Mask {
+    /// A mask `cols` cells wide, or an error if no cell is carveable or the
+    /// rows wouldn't be equally long.
+    pub fn new(cols: usize, carveable: Vec<bool>) -> Result<Self, String> {
+        if cols == 0 || !carveable.len().is_multiple_of(cols) {
+            return Err(format!(
+                "{} cells don't fill rows of {} cells",
+                carveable.len(),
+                cols
+            ));
+        }
+        if !carveable.contains(&true) {
+            return Err("the mask has no cells to carve".to_string());
+        }
+        return Ok(Self {
+            rows: carveable.len() / cols,
+            cols,
+            carveable,
+        });
+    }
+
+    /// Reads a mask from an image with a cell for every pixel, where dark
+    /// pixels can be carved and light or transparent ones are blocked. Only
+    /// the largest connected part of the dark pixels ends up in the maze.
+    #[cfg(feature = "mask")]
+    pub fn load(path: &str) -> Result<Self, String> {
+        let image = image::open(path)
+            .map_err(|err| format!("could not load the mask {}: {}", path, err))?
+            .to_luma_alpha8();
+        let carveable: Vec<bool> = image
+            .pixels()
+            .map(|pixel| pixel.0[0] < 128 && pixel.0[1] >= 128)
+            .collect();
+        return Self::new(image.width() as usize, carveable)
+            .map_err(|err| format!("could not use the mask {}: {}", path, err));
+    }
+
+    pub fn rows(&self) -> usize {
+        return self.rows;
+    }
+
+    pub fn cols(&self) -> usize {
+        return self.cols;
+    }
+}
+
+/// How the passages of a maze are shaped, counted by cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    pub cells: usize,
+    /// Cells with a single way out.
+    pub dead_ends: usize,
+    /// Cells with three or more ways out.
+    pub junctions: usize,
+    /// Cells with two ways out on opposite sides.
+    pub straights: usize,
+    pub visited: usize,
+}
+
+pub struct Grid {
+    pub rows: usize,
+    pub cols: usize,
+    pub topology: Topology,
+    /// Index of the first cell of every ring, and the number of cells, on polar
+    /// grids.
+    ring_starts: Vec<usize>,
+    /// Stacked copies of the grid, connected by stairs between cells at the
+    /// same position. Cells are stored one layer after the other.
+    pub layers: usize,
+    /// The only layer that is drawn, and the one `cell_at` looks at.
+    pub shown_layer: usize,
+    /// Whether the leftmost column neighbors the rightmost one. Only possible
+    /// on grids at least three cells wide, and for triangles only with an even
+    /// number of columns so they keep pointing up and down in turns.
+    pub wrap_x: bool,
+    /// Like `wrap_x`, but for the top and bottom row.
+    pub wrap_y: bool,
+    pub seed: u64,
+    rng: fastrand::Rng,
+    pub cells: Vec<Cell>,
+    /// Cells the recursive backtracker carved through to reach `current`, and
+    /// can backtrack to. The growing tree keeps its active list here.
+    pub stack: VecDeque<usize>,
+    pub current: usize,
+    pub next: Option<usize>,
+    pub start: usize,
+    pub goal: usize,
+    pub algorithm: GenAlgorithm,
+    pub finished: bool,
+    frontier: Vec<usize>,
+    walls: Vec<(usize, usize)>,
+    sets: DisjointSet,
+    pub walk: Vec<usize>,
+    pub erased: Vec<usize>,
+    remaining: usize,
+    row_sets: Vec<usize>,
+    next_set: usize,
+    pub active_row: Option<usize>,
+    regions: Vec<(usize, usize, usize, usize)>,
+    pub run: Vec<usize>,
+    run_closed: bool,
+    pub hunt_row: Option<usize>,
+    pub policy: CellPickPolicy,
+    /// First and last row and column of the rooms on the first layer, see
+    /// `carve_room`.
+    pub rooms: Vec<(usize, usize, usize, usize)>,
+    /// How likely the recursive backtracker keeps carving in the direction of
+    /// its last carve instead of picking a random neighbor, from 0 to 1.
+    pub straightness: f32,
+    /// Direction of the last carve of the recursive backtracker, unless it
+    /// took the stairs or backtracked since.
+    last_direction: Option<Vec2>,
+    /// The latest steps of the recursive backtracker, oldest first.
+    history: VecDeque<UndoStep>,
+    pub solver: Option<SolveAlgorithm>,
+    pub queue: VecDeque<usize>,
+    pub came_from: HashMap<usize, usize>,
+    pub open: BinaryHeap<Reverse<(u32, usize)>>,
+    g_scores: Vec<u32>,
+    pub closed: Vec<bool>,
+    pub solution: Option<Vec<usize>>,
+    pub solve_failed: bool,
+    weights: Vec<u32>,
+    pub follower: usize,
+    pub trail: Vec<usize>,
+    /// The cell the player walked to in play mode.
+    pub player: usize,
+    /// Pairs of cells that take the player straight to each other, see
+    /// `add_portal`.
+    pub portals: Vec<(usize, usize)>,
+    /// The way from the start to the goal that `auto_solve_step` walks along,
+    /// and the step of it it has got to.
+    pub auto_route: Vec<usize>,
+    pub auto_position: usize,
+    /// The cells with a coin the player still has to pick up before the goal
+    /// counts, see `scatter_coins`.
+    pub coins: Vec<usize>,
+    pub coins_collected: usize,
+    /// Set whenever a wall is opened or closed, so that the wall mesh can be
+    /// built again.
+    pub walls_changed: bool,
+    /// How wide and high square cells are, see `set_cell_size`.
+    cell_w: f32,
+    cell_h: f32,
+    /// How many cells are visited and how many aren't blocked, counted as
+    /// they change so `progress` needn't look at every cell.
+    visited_count: usize,
+    carved_count: usize,
+}
+
+impl Grid {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        return Self::with_algorithm(rows, cols, GenAlgorithm::RecursiveBacktracker);
+    }
+
+    /// Same seed and dimensions always result in the same maze.
+    pub fn new_seeded(rows: usize, cols: usize, seed: u64) -> Self {
+        return Self::with_seed(rows, cols, GenAlgorithm::RecursiveBacktracker, seed);
+    }
+
+    pub fn with_algorithm(rows: usize, cols: usize, algorithm: GenAlgorithm) -> Self {
+        return Self::with_seed(rows, cols, algorithm, fastrand::u64(..));
+    }
+
+    pub fn with_seed(rows: usize, cols: usize, algorithm: GenAlgorithm, seed: u64) -> Self {
+        return Self::with_topology(rows, cols, Topology::Square, algorithm, seed);
+    }
+
+    /// Like `with_seed`, but the cells are shaped and connected by `topology`.
+    pub fn with_topology(
+        rows: usize,
+        cols: usize,
+        topology: Topology,
+        algorithm: GenAlgorithm,
+        seed: u64,
+    ) -> Self {
+        return Self::with_layers(rows, cols, 1, topology, (false, false), algorithm, seed);
+    }
+
+    /// Like `with_topology`, but with `layers` grids on top of each other and
+    /// wrapping around as set by `wrap`, which is `(wrap_x, wrap_y)`. Falls
+    /// back to the recursive backtracker if `algorithm` doesn't support them.
+    /// Sizes of 0 are taken as 1, and a grid of one cell is finished from the
+    /// start.
+    pub fn with_layers(
+        rows: usize,
+        cols: usize,
+        layers: usize,
+        topology: Topology,
+        wrap: (bool, bool),
+        algorithm: GenAlgorithm,
+        seed: u64,
+    ) -> Self {
+        let algorithm = if algorithm.supports(topology, layers) {
+            algorithm
+        } else {
+            GenAlgorithm::RecursiveBacktracker
+        };
+        let (rows, layers) = (rows.max(1), layers.max(1));
+        // a single column of triangles falls apart into pairs
+        let cols = if topology == Topology::Triangle {
+            cols.max(2)
+        } else {
+            cols.max(1)
+        };
+        let mut grid = Self::blank(rows, cols, layers, topology, algorithm, seed);
+        grid.wrap_x =
+            wrap.0 && cols > 2 && (topology != Topology::Triangle || cols.is_multiple_of(2));
+        grid.wrap_y =
+            wrap.1 && rows > 2 && (topology != Topology::Triangle || rows.is_multiple_of(2));
+        if topology == Topology::Polar {
+            // the rings already wrap around
+            (grid.wrap_x, grid.wrap_y) = (false, false);
+        }
+        grid.begin();
+        return grid;
+    }
+
+    /// A square grid the size of `mask`, where only the cells of the largest
+    /// connected part of the mask are carved and the rest are blocked. Falls
+    /// back to the recursive backtracker if `algorithm` can't leave blocked
+    /// cells alone, see `GenAlgorithm::grows`.
+    pub fn with_mask(mask: &Mask, algorithm: GenAlgorithm, seed: u64) -> Self {
+        let algorithm = if algorithm.grows() {
+            algorithm
+        } else {
+            GenAlgorithm::RecursiveBacktracker
+        };
+        let mut grid = Self::blank(mask.rows, mask.cols, 1, Topology::Square, algorithm, seed);
+
+        let mut part = vec![usize::MAX; grid.cells.len()];
+        let mut sizes: Vec<usize> = Vec::new();
+        for index in 0..grid.cells.len() {
+            if !mask.carveable[index] || part[index] != usize::MAX {
+                continue;
+            }
+            part[index] = sizes.len();
+            let mut size = 0;
+            let mut queue = VecDeque::from([index]);
+            while let Some(cell) = queue.pop_front() {
+                size += 1;
+                for neighbor in grid.neighbors(cell) {
+                    if mask.carveable[neighbor] && part[neighbor] == usize::MAX {
+                        part[neighbor] = sizes.len();
+                        queue.push_back(neighbor);
+                    }
+                }
+            }
+            sizes.push(size);
+        }
+        let largest = (0..sizes.len()).max_by_key(|&part| sizes[part]).unwrap();
+        let carved: Vec<bool> = part.iter().map(|&part| part == largest).collect();
+
+        let cells: Vec<usize> = (0..grid.cells.len())
+            .filter(|&index| carved[index])
+            .collect();
+        grid.current = cells[grid.rng.usize(..cells.len())];
+        grid.keep_cells(&carved);
+        grid.begin();
+        return grid;
+    }
+
+    /// Like `with_mask`, with the mask loaded from an image, see `Mask::load`.
+    #[cfg(feature = "mask")]
+    pub fn from_mask(path: &str, algorithm: GenAlgorithm, seed: u64) -> Result<Self, String> {
+        return Ok(Self::with_mask(&Mask::load(path)?, algorithm, seed));
+    }
+
+    /// Takes the first step of the algorithm, or gets it ready to take one.
+    fn begin(&mut self) {
+        match self.algorithm {
+            GenAlgorithm::RecursiveBacktracker => self.update_current(),
+            GenAlgorithm::Prim => self.init_prim(),
+            GenAlgorithm::Kruskal => self.init_kruskal(),
+            GenAlgorithm::Wilson => self.init_wilson(),
+            GenAlgorithm::AldousBroder => self.init_aldous_broder(),
+            GenAlgorithm::Eller => self.init_eller(),
+            GenAlgorithm::RecursiveDivision => self.init_recursive_division(),
+            GenAlgorithm::BinaryTree | GenAlgorithm::Sidewinder => self.current = 0,
+            GenAlgorithm::HuntAndKill => self.visit(self.current),
+            GenAlgorithm::GrowingTree => {
+                self.visit(self.current);
+                self.stack.push_back(self.current);
+            }
+        }
+        // a single cell has nothing left to carve
+        if self.finished {
+            self.end_generation();
+        }
+        while self.carved_count == 1 && !self.finished {
+            self.step();
+        }
+    }
+
+    /// A grid with every wall in place, before `algorithm` took its first step.
+    fn blank(
+        rows: usize,
+        cols: usize,
+        layers: usize,
+        topology: Topology,
+        algorithm: GenAlgorithm,
+        seed: u64,
+    ) -> Self {
+        let rng = fastrand::Rng::with_seed(seed);
+        let mut cells: Vec<Cell> = Vec::new();
+        let mut ring_starts: Vec<usize> = Vec::new();
+
+        let widths = match topology {
+            Topology::Polar => ring_sizes(rows),
+            _ => vec![cols; rows],
+        };
+        for (row, &width) in widths.iter().enumerate() {
+            if topology == Topology::Polar {
+                ring_starts.push(cells.len());
+            }
+            for col in 0..width {
+                cells.push(Cell {
+                    row,
+                    col,
+                    ..Default::default()
+                });
+            }
+        }
+        if topology == Topology::Polar {
+            ring_starts.push(cells.len());
+        }
+        let layer: Vec<Cell> = cells.clone();
+        for above in 1..layers {
+            cells.extend(layer.iter().map(|&cell| Cell {
+                layer: above,
+                ..cell
+            }));
+        }
+        let size = cells.len();
+
+        return Self {
+            rows,
+            cols: widths.last().copied().unwrap_or(cols),
+            topology,
+            ring_starts,
+            layers,
+            shown_layer: 0,
+            wrap_x: false,
+            wrap_y: false,
+            seed,
+            cells,
+            stack: VecDeque::new(),
+            current: rng.usize(..size),
+            rng,
+            next: None,
+            start: 0,
+            goal: size - 1,
+            algorithm,
+            finished: false,
+            frontier: Vec::new(),
+            walls: Vec::new(),
+            sets: DisjointSet::new(0),
+            walk: Vec::new(),
+            erased: Vec::new(),
+            remaining: 0,
+            row_sets: Vec::new(),
+            next_set: 0,
+            active_row: None,
+            regions: Vec::new(),
+            run: Vec::new(),
+            run_closed: false,
+            hunt_row: None,
+            policy: CellPickPolicy::Newest,
+            rooms: Vec::new(),
+            straightness: 0.0,
+            last_direction: None,
+            history: VecDeque::new(),
+            solver: None,
+            queue: VecDeque::new(),
+            came_from: HashMap::new(),
+            open: BinaryHeap::new(),
+            g_scores: Vec::new(),
+            closed: Vec::new(),
+            solution: None,
+            solve_failed: false,
+            weights: vec![1; size],
+            follower: 0,
+            trail: Vec::new(),
+            player: 0,
+            portals: Vec::new(),
+            auto_route: Vec::new(),
+            auto_position: 0,
+            coins: Vec::new(),
+            coins_collected: 0,
+            walls_changed: true,
+            cell_w: CELL_SIZE,
+            cell_h: CELL_SIZE,
+            visited_count: 0,
+            carved_count: size,
+        };
+    }
+
+    /// Parses the format written by `to_ascii` back into a finished maze.
+    pub fn from_ascii(text: &str) -> Result<Grid, ParseError> {
+        let lines: Vec<&str> = text
+            .trim_end()
+            .lines()
+            .map(|line| line.trim_end_matches('\r'))
+            .collect();
+        if lines.len() < 3 || lines.len().is_multiple_of(2) {
+            return Err(ParseError::new(
+                lines.len(),
+                "expected an odd number of lines, at least 3",
+            ));
+        }
+
+        let width = lines[0].len();
+        if width < 5 || !(width - 1).is_multiple_of(4) || !lines[0].is_ascii() {
+            return Err(ParseError::new(1, "expected `+---+` segments"));
+        }
+
+        let rows = lines.len() / 2;
+        let cols = (width - 1) / 4;
+        let mut grid = Self::blank(
+            rows,
+            cols,
+            1,
+            Topology::Square,
+            GenAlgorithm::RecursiveBacktracker,
+            0,
+        );
+
+        for (number, line) in lines.iter().enumerate() {
+            if line.len() != width || !line.is_ascii() {
+                return Err(ParseError::new(
+                    number + 1,
+                    &format!("expected {} characters, found {}", width, line.len()),
+                ));
+            }
+            let bytes = line.as_bytes();
+
+            if number.is_multiple_of(2) {
+                // a line of corners and horizontal walls
+                for col in 0..cols {
+                    let wall = match (bytes[col * 4], &line[col * 4 + 1..col * 4 + 4]) {
+                        (b'+', "---") => true,
+                        (b'+', "   ") => false,
+                        _ => return Err(ParseError::new(number + 1, "expected `+---` or `+   `")),
+                    };
+                    if number / 2 < rows {
+                        grid.cells[number / 2 * cols + col]
+                            .walls
+                            .set(Walls::TOP, wall);
+                    }
+                    if number > 0 {
+                        grid.cells[(number / 2 - 1) * cols + col]
+                            .walls
+                            .set(Walls::BOTTOM, wall);
+                    }
+                }
+                if bytes[width - 1] != b'+' {
+                    return Err(ParseError::new(number + 1, "expected a closing `+`"));
+                }
+            } else {
+                // a line of vertical walls and cell contents
+                let row = number / 2;
+                for col in 0..=cols {
+                    let wall = match bytes[col * 4] {
+                        b'|' => true,
+                        b' ' => false,
+                        _ => return Err(ParseError::new(number + 1, "expected `|` or ` `")),
+                    };
+                    if col < cols && &line[col * 4 + 1..col * 4 + 4] != "   " {
+                        return Err(ParseError::new(
+                            number + 1,
+                            "expected three spaces per cell",
+                        ));
+                    }
+                    if col < cols {
+                        grid.cells[row * cols + col].walls.set(Walls::LEFT, wall);
+                    }
+                    if col > 0 {
+                        grid.cells[row * cols + col - 1]
+                            .walls
+                            .set(Walls::RIGHT, wall);
+                    }
+                }
+            }
+        }
+
+        for cell in grid.cells.iter_mut() {
+            cell.visited = true;
+        }
+        grid.visited_count = grid.cells.len();
+        grid.finished = true;
+        return Ok(grid);
+    }
+
+    /// Runs the selected generation algorithm until the maze is complete.
+    pub fn generate_full(&mut self) {
+        while !self.finished {
+            self.step();
+        }
+        debug_assert!(
+            !self.is_perfect() || self.is_solvable(),
+            "{} generated a perfect maze without a way from start to goal",
+            self.algorithm.name()
+        );
+    }
+
+    /// Like `generate_full`, but splits the grid into up to `regions`
+    /// rectangles that are generated on threads of their own, and then joins
+    /// them through a single opening between neighboring rectangles, picked
+    /// like Kruskal's algorithm picks walls, so the maze stays perfect. Only
+    /// square grids with a single layer, no rooms and no blocked cells are
+    /// split; the rest is generated as usual. Whatever the generator carved
+    /// before is walled up again.
+    pub fn generate_parallel(&mut self, regions: usize) {
+        let blocked = self.cells.iter().any(|cell| cell.blocked);
+        if regions < 2
+            || self.topology != Topology::Square
+            || self.layers != 1
+            || !self.rooms.is_empty()
+            || blocked
+            || self.finished
+        {
+            self.generate_full();
+            return;
+        }
+
+        // about as many rows of rectangles as columns of them
+        let tile_rows = ((regions as f32).sqrt() as usize).clamp(1, self.rows);
+        let tile_cols = (regions / tile_rows).clamp(1, self.cols);
+        let row_bounds: Vec<usize> = (0..=tile_rows)
+            .map(|tile| tile * self.rows / tile_rows)
+            .collect();
+        let col_bounds: Vec<usize> = (0..=tile_cols)
+            .map(|tile| tile * self.cols / tile_cols)
+            .collect();
+        let tiles: Vec<(usize, usize, u64)> = (0..tile_rows * tile_cols)
+            .map(|tile| (tile / tile_cols, tile % tile_cols, self.rng.u64(..)))
+            .collect();
+
+        let algorithm = self.algorithm;
+        let carved: Vec<Grid> = std::thread::scope(|scope| {
+            let handles: Vec<_> = tiles
+                .iter()
+                .map(|&(tile_row, tile_col, seed)| {
+                    let rows = row_bounds[tile_row + 1] - row_bounds[tile_row];
+                    let cols = col_bounds[tile_col + 1] - col_bounds[tile_col];
+                    return scope.spawn(move || {
+                        let mut tile = Grid::with_seed(rows, cols, algorithm, seed);
+                        tile.generate_full();
+                        return tile;
+                    });
+                })
+                .collect();
+            return handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect();
+        });
+
+        // copy the walls of each rectangle, but not its entrance and exit
+        for (&(tile_row, tile_col, _), tile) in tiles.iter().zip(carved.iter()) {
+            let (row0, col0) = (row_bounds[tile_row], col_bounds[tile_col]);
+            for cell in tile.cells.iter() {
+                let copy = &mut self.cells[(row0 + cell.row) * self.cols + col0 + cell.col];
+                let border = [
+                    (Walls::TOP, cell.row == 0),
+                    (Walls::BOTTOM, cell.row + 1 == tile.rows),
+                    (Walls::LEFT, cell.col == 0),
+                    (Walls::RIGHT, cell.col + 1 == tile.cols),
+                ];
+                for (wall, on_border) in border {
+                    copy.walls.set(wall, cell.walls.contains(wall) || on_border);
+                }
+            }
+        }
+        self.walls_changed = true;
+
+        // one random opening along the border of each pair that gets joined
+        // the rectangle on the left or above, the other one, and whether it's
+        // on the right
+        let mut borders: Vec<(usize, usize, bool)> = Vec::new();
+        for tile in 0..tiles.len() {
+            if (tile + 1) % tile_cols != 0 {
+                borders.push((tile, tile + 1, true));
+            }
+            if tile + tile_cols < tiles.len() {
+                borders.push((tile, tile + tile_cols, false));
+            }
+        }
+        self.rng.shuffle(&mut borders);
+        let mut sets = DisjointSet::new(tiles.len());
+        for (a, b, beside) in borders {
+            if !sets.union(a, b) {
+                continue;
+            }
+            let (tile_row, tile_col, _) = tiles[a];
+            let index = if beside {
+                let row = self
+                    .rng
+                    .usize(row_bounds[tile_row]..row_bounds[tile_row + 1]);
+                row * self.cols + col_bounds[tile_col + 1] - 1
+            } else {
+                let col = self
+                    .rng
+                    .usize(col_bounds[tile_col]..col_bounds[tile_col + 1]);
+                (row_bounds[tile_row + 1] - 1) * self.cols + col
+            };
+            let neighbor = if beside { index + 1 } else { index + self.cols };
+            self.set_wall(index, neighbor, false);
+        }
+
+        for index in 0..self.cells.len() {
+            self.visit(index);
+        }
+        self.stack.clear();
+        self.frontier.clear();
+        self.walls.clear();
+        self.walk.clear();
+        self.erased.clear();
+        self.run.clear();
+        self.regions.clear();
+        (self.active_row, self.hunt_row) = (None, None);
+        self.history.clear();
+        self.next = None;
+        self.finished = true;
+        self.open_entrance();
+        self.open_exit();
+        debug_assert!(
+            self.is_perfect(),
+            "the regions weren't joined into a perfect maze"
+        );
+    }
+
+    /// Advances the selected generation algorithm by a single step.
+    pub fn step(&mut self) {
+        if self.finished {
+            return;
+        }
+        match self.algorithm {
+            GenAlgorithm::RecursiveBacktracker => self.update_current(),
+            GenAlgorithm::Prim => self.update_prim(),
+            GenAlgorithm::Kruskal => self.update_kruskal(),
+            GenAlgorithm::Wilson => self.update_wilson(),
+            GenAlgorithm::AldousBroder => self.update_aldous_broder(),
+            GenAlgorithm::Eller => self.update_eller(),
+            GenAlgorithm::RecursiveDivision => self.update_recursive_division(),
+            GenAlgorithm::BinaryTree => self.update_binary_tree(),
+            GenAlgorithm::Sidewinder => self.update_sidewinder(),
+            GenAlgorithm::HuntAndKill => self.update_hunt_and_kill(),
+            GenAlgorithm::GrowingTree => self.update_growing_tree(),
+        }
+        if self.finished {
+            self.end_generation();
+        }
+        debug_assert!(
+            !self.finished || self.is_perfect() || !self.rooms.is_empty() && self.is_connected(),
+            "{} generated a maze that isn't perfect",
+            self.algorithm.name()
+        );
+    }
+
+    /// What's left to do once the algorithm has carved the whole maze.
+    fn end_generation(&mut self) {
+        if !self.rooms.is_empty() {
+            self.connect_rooms();
+        }
+        self.open_entrance();
+        self.open_exit();
+    }
+
+    pub fn checked_index(&self, row: usize, col: usize) -> Result<usize, String> {
+        if let Some(index) = self.cell_index(row as i32, col as i32) {
+            return Ok(index);
+        }
+        return Err(format!(
+            "cell ({}, {}) is outside of the {}x{} grid",
+            row, col, self.rows, self.cols
+        ));
+    }
+
+    pub fn set_start(&mut self, row: usize, col: usize) -> Result<(), String> {
+        self.start = self.checked_index(row, col)?;
+        return Ok(());
+    }
+
+    pub fn set_goal(&mut self, row: usize, col: usize) -> Result<(), String> {
+        self.goal = self.checked_index(row, col)?;
+        return Ok(());
+    }
+
+    /// The lines of the walls of the cell at `index` that are in place.
+    pub fn walls(&self, index: usize) -> Vec<(Vec2, Vec2)> {
+        let cell = &self.cells[index];
+        return match self.topology {
+            Topology::Square => cell.walls(self.cell_size()),
+            Topology::Hex => cell.hex_walls(),
+            Topology::Triangle => cell.triangle_walls(),
+            Topology::Polar => {
+                let size = self.row_len(cell.row);
+                cell.polar_walls(self.polar_origin(), size, cell.row + 1 == self.rows)
+            }
+        };
+    }
+
+    pub fn center(&self, index: usize) -> Vec2 {
+        return match self.topology {
+            Topology::Square => self.cells[index].center(self.cell_size()),
+            Topology::Hex => self.cells[index].hex_center(),
+            Topology::Triangle => self.cells[index].triangle_center(),
+            Topology::Polar => {
+                let size = self.row_len(self.cells[index].row);
+                self.cells[index].polar_center(self.polar_origin(), size)
+            }
+        };
+    }
+
+    /// How wide and high square cells are drawn, `CELL_SIZE` both unless
+    /// `set_cell_size` stretched them.
+    pub fn cell_size(&self) -> (f32, f32) {
+        return (self.cell_w, self.cell_h);
+    }
+
+    /// Makes square cells `cell_w` wide and `cell_h` high, say to fill a
+    /// window of another shape. Other cells keep their shape.
+    pub fn set_cell_size(&mut self, (cell_w, cell_h): (f32, f32)) {
+        if (cell_w, cell_h) != (self.cell_w, self.cell_h) {
+            (self.cell_w, self.cell_h) = (cell_w, cell_h);
+            self.walls_changed = true;
+        }
+    }
+
+    /// Where the hub of a polar grid is drawn, so that the outermost ring
+    /// touches the top and left edges.
+    pub fn polar_origin(&self) -> Vec2 {
+        return Vec2::splat(self.rows as f32 * RING_WIDTH + WALL_WIDTH);
+    }
+
+    /// The cell of `shown_layer` drawn at `point`, in the same coordinates the
+    /// cells are drawn in.
+    pub fn cell_at(&self, point: Vec2) -> Option<usize> {
+        let (row, col) = match self.topology {
+            Topology::Square => (
+                (point.y / self.cell_h).floor(),
+                (point.x / self.cell_w).floor(),
+            ),
+            Topology::Hex => {
+                // round to the nearest hexagon in cube coordinates
+                let row = (point.y / HEX_RADIUS - 1.0) / 1.5;
+                let col = point.x / HEX_WIDTH - 0.5 - row / 2.0;
+                let (mut r, mut q, s) = (row.round(), col.round(), (-row - col).round());
+                let (dr, dq, ds) = ((r - row).abs(), (q - col).abs(), (s + row + col).abs());
+                if dr > dq && dr > ds {
+                    r = -q - s;
+                } else if dq > ds {
+                    q = -r - s;
+                }
+                (r, q)
+            }
+            Topology::Triangle => {
+                let row = (point.y / TRIANGLE_HEIGHT).floor();
+                let col = (point.x / (TRIANGLE_SIDE / 2.0)).floor();
+                // the slanted sides cut into the neighbors on both sides
+                let inside = |col: f32| -> bool {
+                    let cell = match self.cell_index(row as i32, col as i32) {
+                        Some(index) => &self.cells[index],
+                        None => return false,
+                    };
+                    let [a, b, c] = cell.triangle_corners();
+                    let side = |from: Vec2, to: Vec2| (to - from).perp_dot(point - from);
+                    let sides = [side(a, b), side(b, c), side(c, a)];
+                    return sides.iter().all(|&d| d >= 0.0) || sides.iter().all(|&d| d <= 0.0);
+                };
+                let col = [col, col - 1.0, col + 1.0]
+                    .into_iter()
+                    .find(|&col| inside(col))
+                    .unwrap_or(-1.0);
+                (row, col)
+            }
+            Topology::Polar => {
+                let offset = point - self.polar_origin();
+                let row = (offset.length() / RING_WIDTH).floor();
+                if row < 0.0 || row as usize >= self.rows {
+                    return None;
+                }
+                let turn = (offset.y.atan2(offset.x) + std::f32::consts::FRAC_PI_2)
+                    .rem_euclid(std::f32::consts::TAU);
+                let size = self.row_len(row as usize) as f32;
+                (
+                    row,
+                    (turn / std::f32::consts::TAU * size)
+                        .floor()
+                        .min(size - 1.0),
+                )
+            }
+        };
+
+        let index = self.cell_index(row as i32, col as i32)?;
+        return Some(self.shown_layer * self.layer_len() + index);
+    }
+
+    /// Number of cells in each layer.
+    pub fn layer_len(&self) -> usize {
+        return self.cells.len() / self.layers;
+    }
+
+    /// Indices of the cells in `row` of `layer`, which are laid out one row
+    /// after the other.
+    pub fn row_cells(&self, layer: usize, row: usize) -> std::ops::Range<usize> {
+        let start = layer * self.layer_len();
+        if self.topology == Topology::Polar {
+            return start + self.ring_starts[row]..start + self.ring_starts[row + 1];
+        }
+        return start + row * self.cols..start + (row + 1) * self.cols;
+    }
+
+    /// Number of cells in `row`, which only varies on polar grids.
+    pub fn row_len(&self, row: usize) -> usize {
+        return self.row_cells(0, row).len();
+    }
+
+    /// Every cell with its row and column, in the order they are stored: row
+    /// by row, and one layer after the other.
+    pub fn iter_cells(&self) -> impl Iterator<Item = (usize, usize, &Cell)> {
+        return self.cells.iter().map(|cell| (cell.row, cell.col, cell));
+    }
+
+    /// Like `iter_cells`, but the cells can be changed.
+    pub fn iter_cells_mut(&mut self) -> impl Iterator<Item = (usize, usize, &mut Cell)> {
+        return self.cells.iter_mut().map(|cell| (cell.row, cell.col, cell));
+    }
+
+    /// Index of the cell at `row` and `col` of the bottom layer.
+    pub fn cell_index(&self, row: i32, col: i32) -> Option<usize> {
+        if self.topology == Topology::Polar {
+            if row < 0 || row as usize >= self.rows {
+                return None;
+            }
+            let cells = self.row_cells(0, row as usize);
+            if col < 0 || col as usize >= cells.len() {
+                return None;
+            }
+            return Some(cells.start + col as usize);
+        }
+        return index(row, col, self.rows as i32, self.cols as i32);
+    }
+
+    /// The cell in the next ring inwards, on polar grids.
+    fn polar_parent(&self, index: usize) -> Option<usize> {
+        let cell = &self.cells[index];
+        if cell.row == 0 {
+            return None;
+        }
+        let split = self.row_len(cell.row) / self.row_len(cell.row - 1);
+        return Some(self.row_cells(cell.layer, cell.row - 1).start + cell.col / split);
+    }
+
+    /// The cells in the next ring outwards whose parent is `index`, on polar
+    /// grids.
+    fn polar_children(&self, index: usize) -> std::ops::Range<usize> {
+        let cell = &self.cells[index];
+        if cell.row + 1 >= self.rows {
+            return 0..0;
+        }
+        let split = self.row_len(cell.row + 1) / self.row_len(cell.row);
+        let first = self.row_cells(cell.layer, cell.row + 1).start + cell.col * split;
+        return first..first + split;
+    }
+
+    /// Neighbors on a polar grid, clockwise from the outer ones like
+    /// `Topology::directions`: outwards, clockwise, inwards, counterclockwise.
+    fn polar_neighbors(&self, index: usize) -> Neighbors {
+        let cell = &self.cells[index];
+        let ring = self.row_cells(cell.layer, cell.row);
+        let mut neighbors: Neighbors = self.polar_children(index).collect();
+
+        if ring.len() > 1 {
+            neighbors.push(ring.start + (cell.col + 1) % ring.len());
+        }
+        neighbors.extend(self.polar_parent(index));
+        if ring.len() > 1 {
+            neighbors.push(ring.start + (cell.col + ring.len() - 1) % ring.len());
+        }
+        return neighbors;
+    }
+
+    /// `Topology::directions` of the cell at `index`.
+    pub fn directions(&self, index: usize) -> &'static [Direction] {
+        let cell = &self.cells[index];
+        return self.topology.directions(cell.row, cell.col);
+    }
+
+    /// Like `index`, but positions past an edge that wraps continue on the
+    /// other side of the grid.
+    fn wrapped_index(&self, row: i32, col: i32) -> Option<usize> {
+        let (rows, cols) = (self.rows as i32, self.cols as i32);
+        let row = if self.wrap_y {
+            row.rem_euclid(rows)
+        } else {
+            row
+        };
+        let col = if self.wrap_x {
+            col.rem_euclid(cols)
+        } else {
+            col
+        };
+        return index(row, col, rows, cols);
+    }
+
+    /// Whether the passage between the neighbors `a` and `b` leads across a
+    /// wrapping edge.
+    pub fn crosses_seam(&self, a: usize, b: usize) -> bool {
+        let (cell_a, cell_b) = (&self.cells[a], &self.cells[b]);
+        return (self.wrap_y && cell_a.row.abs_diff(cell_b.row) > 1)
+            || (self.wrap_x && cell_a.col.abs_diff(cell_b.col) > 1);
+    }
+
+    /// How far the grid moves to where it repeats past the wrapping edge on the
+    /// right and past the one at the bottom.
+    fn wrap_shift(&self) -> (Vec2, Vec2) {
+        let (rows, cols) = (self.rows as f32, self.cols as f32);
+        return match self.topology {
+            Topology::Square => (vec2(cols * self.cell_w, 0.0), vec2(0.0, rows * self.cell_h)),
+            // polar grids never wrap
+            Topology::Polar => (vec2(cols * CELL_SIZE, 0.0), vec2(0.0, rows * CELL_SIZE)),
+            Topology::Hex => (
+                vec2(cols * HEX_WIDTH, 0.0),
+                vec2(rows * HEX_WIDTH / 2.0, rows * 1.5 * HEX_RADIUS),
+            ),
+            Topology::Triangle => (
+                vec2(cols * TRIANGLE_SIDE / 2.0, 0.0),
+                vec2(0.0, rows * TRIANGLE_HEIGHT),
+            ),
+        };
+    }
+
+    /// Where the neighbor `b` is drawn relative to `a`, as if it was drawn next
+    /// to it when the passage between them crosses a wrapping edge.
+    fn direction(&self, a: usize, b: usize) -> Vec2 {
+        let (cell_a, cell_b) = (&self.cells[a], &self.cells[b]);
+        let (shift_x, shift_y) = self.wrap_shift();
+        let mut direction = self.center(b) - self.center(a);
+        // cells at either end of a passage under a crossing are two apart
+        if self.wrap_x && cell_a.col.abs_diff(cell_b.col) * 2 > self.cols {
+            direction += if cell_b.col < cell_a.col {
+                shift_x
+            } else {
+                -shift_x
+            };
+        }
+        if self.wrap_y && cell_a.row.abs_diff(cell_b.row) * 2 > self.rows {
+            direction += if cell_b.row < cell_a.row {
+                shift_y
+            } else {
+                -shift_y
+            };
+        }
+        return direction;
+    }
+
+    /// Neighbors on the same layer, followed by the ones up and down the stairs.
+    pub fn neighbors(&self, index: usize) -> Neighbors {
+        let cell = &self.cells[index];
+        let layer_start = cell.layer * self.layer_len();
+
+        let mut neighbors: Neighbors = if self.topology == Topology::Polar {
+            self.polar_neighbors(index)
+        } else {
+            self.directions(index)
+                .iter()
+                .filter_map(|direction| {
+                    let (row, col) = direction.offset();
+                    return self.wrapped_index(cell.row as i32 + row, cell.col as i32 + col);
+                })
+                .map(|neighbor| layer_start + neighbor)
+                .collect()
+        };
+        if cell.layer > 0 {
+            neighbors.push(index - self.layer_len());
+        }
+        if cell.layer + 1 < self.layers {
+            neighbors.push(index + self.layer_len());
+        }
+        return neighbors;
+    }
+
+    fn get_random_neighbor(&self) -> Option<usize> {
+        let mut neighbors: Neighbors = self
+            .neighbors(self.current)
+            .into_iter()
+            .filter(|&index| !self.cells[index].visited && !self.cells[index].blocked)
+            .collect();
+
+        if neighbors.is_empty() {
+            return None;
+        }
+
+        let layer = self.current_cell().layer;
+        let level = neighbors
+            .iter()
+            .filter(|&&index| self.cells[index].layer == layer)
+            .count();
+        if level > 0 && (level == neighbors.len() || self.rng.f32() >= STAIR_CHANCE) {
+            neighbors.retain(|index| self.cells[index].layer == layer);
+        }
+
+        return Some(neighbors[self.rng.usize(..neighbors.len())]);
+    }
+
+    /// `get_random_neighbor` as it was before `Neighbors`, with the neighbors
+    /// collected into a `Vec` and filtered into another one, for the tests to
+    /// compare against.
+    #[cfg(test)]
+    fn get_random_neighbor_vec(&self) -> Option<usize> {
+        let all: Vec<usize> = self.neighbors(self.current).to_vec();
+        let mut neighbors: Vec<usize> = all
+            .into_iter()
+            .filter(|&index| !self.cells[index].visited && !self.cells[index].blocked)
+            .collect();
+
+        if neighbors.is_empty() {
+            return None;
+        }
+
+        let layer = self.current_cell().layer;
+        let level = neighbors
+            .iter()
+            .filter(|&&index| self.cells[index].layer == layer)
+            .count();
+        if level > 0 && (level == neighbors.len() || self.rng.f32() >= STAIR_CHANCE) {
+            neighbors.retain(|&index| self.cells[index].layer == layer);
+        }
+
+        return Some(neighbors[self.rng.usize(..neighbors.len())]);
+    }
+
+    /// With a chance of `straightness`, the unvisited neighbor on the same
+    /// layer that continues the last carve straight on, if there is one.
+    fn get_straight_neighbor(&self) -> Option<usize> {
+        let last = self.last_direction?;
+        if self.straightness <= 0.0 || self.rng.f32() >= self.straightness {
+            return None;
+        }
+
+        let layer = self.current_cell().layer;
+        return self
+            .neighbors(self.current)
+            .into_iter()
+            .filter(|&index| {
+                let cell = &self.cells[index];
+                return !cell.visited && !cell.blocked && cell.layer == layer;
+            })
+            .map(|index| {
+                (
+                    index,
+                    self.direction(self.current, index).normalize().dot(last),
+                )
+            })
+            .filter(|&(_, alignment)| alignment >= STRAIGHT_ALIGNMENT)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(index, _)| index);
+    }
+
+    /// Removes the wall between `current` and `next`.
+    fn remove_wall(&mut self, next: usize) {
+        self.set_wall(self.current, next, false);
+    }
+
+    /// The sides of `a` and `b` that face each other, if they are neighbors.
+    /// Only the outer cell of two in different rings of a polar grid has a
+    /// wall between them.
+    fn facing_sides(&self, a: usize, b: usize) -> Option<(Option<Direction>, Option<Direction>)> {
+        let (cell_a, cell_b) = (&self.cells[a], &self.cells[b]);
+        if cell_a.layer != cell_b.layer {
+            return None;
+        }
+
+        if self.topology == Topology::Polar {
+            if self.polar_parent(a) == Some(b) {
+                return Some((Some(Direction::North), None));
+            } else if self.polar_parent(b) == Some(a) {
+                return Some((None, Some(Direction::North)));
+            }
+
+            let size = self.row_len(cell_a.row);
+            if cell_a.row != cell_b.row || size < 2 {
+                return None;
+            } else if (cell_a.col + 1) % size == cell_b.col {
+                return Some((Some(Direction::East), Some(Direction::West)));
+            } else if (cell_b.col + 1) % size == cell_a.col {
+                return Some((Some(Direction::West), Some(Direction::East)));
+            }
+            return None;
+        }
+
+        let (mut row, mut col) = (
+            cell_b.row as i32 - cell_a.row as i32,
+            cell_b.col as i32 - cell_a.col as i32,
+        );
+        // across a wrapping edge the neighbor seems to be on the far side
+        if self.wrap_y && row.unsigned_abs() as usize == self.rows - 1 {
+            row = -row.signum();
+        }
+        if self.wrap_x && col.unsigned_abs() as usize == self.cols - 1 {
+            col = -col.signum();
+        }
+        return Direction::from_offset((row, col))
+            .filter(|direction| self.directions(a).contains(direction))
+            .map(|direction| (Some(direction), Some(direction.opposite())));
+    }
+
+    /// The side of the cell at `index` that faces out of the grid, if it is on
+    /// an edge that doesn't wrap around. Top and bottom come before the sides.
+    pub fn border_side(&self, index: usize) -> Option<Direction> {
+        use Direction::*;
+        let cell = &self.cells[index];
+        if self.topology == Topology::Polar {
+            return (cell.row + 1 == self.rows).then_some(South);
+        }
+
+        let outside = |direction: Direction| -> bool {
+            let (row, col) = direction.offset();
+            return self
+                .wrapped_index(cell.row as i32 + row, cell.col as i32 + col)
+                .is_none();
+        };
+        let sides = self.directions(index);
+        return [North, South, NorthEast, SouthWest, West, East]
+            .into_iter()
+            .find(|&direction| sides.contains(&direction) && outside(direction));
+    }
+
+    /// Removes the wall between the start and the outside, so the maze has a
+    /// way in. Does nothing if the start isn't on the border.
+    pub fn open_entrance(&mut self) {
+        self.set_border(self.start, true);
+    }
+
+    /// Like `open_entrance`, but for the goal.
+    pub fn open_exit(&mut self) {
+        self.set_border(self.goal, true);
+    }
+
+    /// Opens or closes the wall between the cell at `index` and the outside.
+    fn set_border(&mut self, index: usize, open: bool) {
+        if let Some(side) = self.border_side(index) {
+            self.cells[index].walls.set_open(side, open);
+            self.walls_changed = true;
+        }
+    }
+
+    /// Whether `b` is up (`true`) or down the stairs from `a`, if there are
+    /// stairs between them.
+    fn stairs_between(&self, a: usize, b: usize) -> Option<bool> {
+        if a.abs_diff(b) != self.layer_len() {
+            return None;
+        }
+        return Some(b > a);
+    }
+
+    /// Opens or closes the wall between the adjacent cells `a` and `b`.
+    fn set_wall(&mut self, a: usize, b: usize, closed: bool) {
+        self.walls_changed = true;
+        if let Some(up) = self.stairs_between(a, b) {
+            let (lower, upper) = if up { (a, b) } else { (b, a) };
+            self.cells[lower].walls.set(Walls::UP, closed);
+            self.cells[upper].walls.set(Walls::DOWN, closed);
+            return;
+        }
+        if let Some((side_a, side_b)) = self.facing_sides(a, b) {
+            if let Some(side) = side_a {
+                self.cells[a].walls.set_open(side, !closed);
+            }
+            if let Some(side) = side_b {
+                self.cells[b].walls.set_open(side, !closed);
+            }
+        }
+    }
+
+    /// Whether the wall between the adjacent cells `a` and `b` is missing.
+    fn wall_open(&self, a: usize, b: usize) -> bool {
+        if let Some(up) = self.stairs_between(a, b) {
+            let stairs = if up { Walls::UP } else { Walls::DOWN };
+            return !self.cells[a].walls.contains(stairs);
+        }
+        return match self.facing_sides(a, b) {
+            Some((Some(side), _)) => self.cells[a].walls.is_open(side),
+            Some((None, Some(side))) => self.cells[b].walls.is_open(side),
+            _ => false,
+        };
+    }
+
+    /// Where going from `index` towards its `neighbor` leads: the neighbor
+    /// itself, or the cell beyond it when the way goes under a crossing.
+    /// Nothing if there is a wall, or if that side of `index` is an end of the
+    /// passage under it rather than of the one across it.
+    fn passage(&self, index: usize, neighbor: usize) -> Option<usize> {
+        if !self.wall_open(index, neighbor) {
+            return None;
+        }
+        let Some((Some(side), _)) = self.facing_sides(index, neighbor) else {
+            return Some(neighbor);
+        };
+        let under = |cell: usize| -> bool {
+            return self.cells[cell]
+                .under
+                .is_some_and(|axis| axis == side || axis == side.opposite());
+        };
+        if under(index) {
+            return None;
+        }
+        if under(neighbor) {
+            return self.beyond(neighbor, side);
+        }
+        return Some(neighbor);
+    }
+
+    /// The neighbor of `index` on its side facing `direction`.
+    fn beyond(&self, index: usize, direction: Direction) -> Option<usize> {
+        let cell = &self.cells[index];
+        let (row, col) = direction.offset();
+        return self
+            .wrapped_index(cell.row as i32 + row, cell.col as i32 + col)
+            .map(|neighbor| cell.layer * self.layer_len() + neighbor);
+    }
+
+    /// Whether there is a passage between `a` and `b`, which are either
+    /// adjacent or at the two ends of a passage under a crossing.
+    pub fn is_open(&self, a: usize, b: usize) -> bool {
+        return self
+            .neighbors(a)
+            .into_iter()
+            .any(|neighbor| self.passage(a, neighbor) == Some(b));
+    }
+
+    /// The two cells on either side of the wall of `shown_layer` closest to
+    /// `point`, if it's close enough to a wall between two cells of the maze.
+    pub fn wall_at(&self, point: Vec2) -> Option<(usize, usize)> {
+        let index = self.cell_at(point)?;
+        let distance = |index: usize| point.distance(self.center(index));
+        let neighbor = self
+            .neighbors(index)
+            .into_iter()
+            .filter(|&neighbor| {
+                return self.cells[neighbor].layer == self.cells[index].layer
+                    && !self.crosses_seam(index, neighbor);
+            })
+            .min_by(|&a, &b| distance(a).total_cmp(&distance(b)))?;
+        let reach = WALL_REACH * self.center(index).distance(self.center(neighbor));
+        if distance(neighbor) - distance(index) > reach {
+            return None;
+        }
+        return Some((index, neighbor));
+    }
+
+    /// Opens or closes the wall between the adjacent cells `a` and `b` by hand,
+    /// on both of their sides. Walls of blocked cells and around crossings stay
+    /// as they are.
+    pub fn edit_wall(&mut self, a: usize, b: usize, closed: bool) -> Result<(), String> {
+        if !self.neighbors(a).contains(&b) {
+            return Err(format!("cells {} and {} aren't next to each other", a, b));
+        }
+        if self.cells[a].blocked || self.cells[b].blocked {
+            return Err("the walls of blocked cells can't be changed".to_string());
+        }
+        if self.cells[a].under.is_some() || self.cells[b].under.is_some() {
+            return Err("the walls around a crossing can't be changed".to_string());
+        }
+        self.set_wall(a, b, closed);
+        self.clear_solution();
+        return Ok(());
+    }
+
+    /// The cells that can be reached from `index` in a single step.
+    pub fn open_neighbors(&self, index: usize) -> Neighbors {
+        return self
+            .neighbors(index)
+            .into_iter()
+            .filter_map(|neighbor| self.passage(index, neighbor))
+            .collect();
+    }
+
+    /// Moves the player to the open neighbor on the same layer that lines up
+    /// best with `direction` on screen. Walls are never crossed, and neither
+    /// are passages that lead too far away from `direction`. Returns whether
+    /// the player moved.
+    pub fn move_player(&mut self, direction: Vec2) -> bool {
+        return self.move_player_to(self.step_towards(self.player, direction));
+    }
+
+    /// Where `move_player` would take a player on the cell at `from`.
+    pub fn step_towards(&self, from: usize, direction: Vec2) -> Option<usize> {
+        let layer = self.cells[from].layer;
+        let target = self
+            .open_neighbors(from)
+            .into_iter()
+            .filter(|&neighbor| self.cells[neighbor].layer == layer)
+            .map(|neighbor| {
+                let towards = self.direction(from, neighbor).normalize();
+                return (towards.dot(direction.normalize()), neighbor);
+            })
+            .filter(|&(alignment, _)| alignment >= MIN_ALIGNMENT)
+            .max_by(|a, b| a.0.total_cmp(&b.0));
+        return target.map(|(_, target)| self.teleport(target));
+    }
+
+    fn move_player_to(&mut self, target: Option<usize>) -> bool {
+        if let Some(target) = target {
+            self.player = target;
+            return true;
+        }
+        return false;
+    }
+
+    /// Moves the player across a corner of a square cell, to the cell that a
+    /// `direction` with a horizontal and a vertical part points at, if it can
+    /// be reached with one step along each of them. Moving diagonally never
+    /// cuts a corner through a wall that way. Returns whether the player moved.
+    pub fn move_player_diagonally(&mut self, direction: Vec2) -> bool {
+        return self.move_player_to(self.step_diagonally(self.player, direction));
+    }
+
+    /// Where `move_player_diagonally` would take a player on the cell at `from`.
+    pub fn step_diagonally(&self, from: usize, direction: Vec2) -> Option<usize> {
+        if self.topology != Topology::Square || direction.x == 0.0 || direction.y == 0.0 {
+            return None;
+        }
+
+        let layer = self.cells[from].layer;
+        let step = |from: usize, axis: Vec2| -> Option<usize> {
+            return self.open_neighbors(from).into_iter().find(|&neighbor| {
+                return self.cells[neighbor].layer == layer
+                    && self.direction(from, neighbor).normalize().dot(axis) >= STRAIGHT_ALIGNMENT;
+            });
+        };
+        let (x, y) = (
+            vec2(direction.x.signum(), 0.0),
+            vec2(0.0, direction.y.signum()),
+        );
+        let target = [(x, y), (y, x)]
+            .into_iter()
+            .find_map(|(first, second)| step(from, first).and_then(|via| step(via, second)));
+        return target.map(|target| self.teleport(target));
+    }
+
+    /// The cells at most `radius` steps through open passages away from
+    /// `source`, including `source` itself.
+    pub fn cells_within(&self, source: usize, radius: usize) -> Vec<usize> {
+        let mut steps = HashMap::from([(source, 0)]);
+        let mut queue = VecDeque::from([source]);
+        while let Some(index) = queue.pop_front() {
+            let distance = steps[&index];
+            if distance == radius {
+                continue;
+            }
+            for neighbor in self.open_neighbors(index) {
+                if let Entry::Vacant(entry) = steps.entry(neighbor) {
+                    entry.insert(distance + 1);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        return steps.into_keys().collect();
+    }
+
+    /// Takes the player up or down the stairs, if they are open. Returns
+    /// whether the player moved.
+    pub fn climb_player(&mut self, up: bool) -> bool {
+        return self.move_player_to(self.climb_from(self.player, up));
+    }
+
+    /// Where `climb_player` would take a player on the cell at `from`.
+    pub fn climb_from(&self, from: usize, up: bool) -> Option<usize> {
+        let layer = self.cells[from].layer;
+        let target = if up && layer + 1 < self.layers {
+            from + self.layer_len()
+        } else if !up && layer > 0 {
+            from - self.layer_len()
+        } else {
+            return None;
+        };
+        if !self.is_open(from, target) {
+            return None;
+        }
+        return Some(self.teleport(target));
+    }
+
+    /// The other end of the portal at `index`, if there is one.
+    pub fn portal_partner(&self, index: usize) -> Option<usize> {
+        return self.portals.iter().find_map(|&(a, b)| {
+            if a == index {
+                return Some(b);
+            } else if b == index {
+                return Some(a);
+            }
+            return None;
+        });
+    }
+
+    /// Where stepping onto `index` leaves the player: the other end of the
+    /// portal there, or `index` itself.
+    fn teleport(&self, index: usize) -> usize {
+        return self.portal_partner(index).unwrap_or(index);
+    }
+
+    /// Like `open_neighbors`, but a step onto a portal leads to the other end
+    /// of it right away. This is how the player and the solvers get around.
+    pub fn moves(&self, index: usize) -> Neighbors {
+        return self
+            .open_neighbors(index)
+            .into_iter()
+            .map(|neighbor| self.teleport(neighbor))
+            .collect();
+    }
+
+    /// Whether `index` can be an end of a new portal: not the start or goal,
+    /// blocked or a crossing, and not one end of a portal already.
+    fn portal_fits(&self, index: usize) -> bool {
+        let cell = &self.cells[index];
+        return index != self.start
+            && index != self.goal
+            && !cell.blocked
+            && cell.under.is_none()
+            && self.portal_partner(index).is_none();
+    }
+
+    /// Connects `a` and `b` with a portal, or returns why they can't be. A
+    /// portal that would cut the goal off from the start isn't added.
+    pub fn add_portal(&mut self, a: usize, b: usize) -> Result<(), String> {
+        if a == b {
+            return Err("a portal needs two different cells".to_string());
+        }
+        for index in [a, b] {
+            if !self.portal_fits(index) {
+                return Err(format!(
+                    "({}, {}) can't be the end of a portal",
+                    self.cells[index].row, self.cells[index].col
+                ));
+            }
+        }
+        let solvable = self.is_solvable();
+        self.portals.push((a, b));
+        if solvable && !self.is_solvable() {
+            self.portals.pop();
+            return Err("the portal would cut the goal off from the start".to_string());
+        }
+        self.clear_solution();
+        return Ok(());
+    }
+
+    /// Adds up to `pairs` portals between random cells, see `add_portal`.
+    pub fn add_portals(&mut self, pairs: usize) {
+        let mut free: Vec<usize> = (0..self.cells.len())
+            .filter(|&index| self.portal_fits(index))
+            .collect();
+        self.rng.shuffle(&mut free);
+        let mut added = 0;
+        for ends in free.chunks_exact(2) {
+            if added == pairs {
+                break;
+            }
+            if self.add_portal(ends[0], ends[1]).is_ok() {
+                added += 1;
+            }
+        }
+    }
+
+    /// Puts up to `count` coins on random cells that can be reached from the
+    /// start, other than the start and the goal, and forgets the ones that
+    /// were collected before.
+    pub fn scatter_coins(&mut self, count: usize) {
+        let distances = self.distances_from(self.start);
+        let mut free: Vec<usize> = (0..self.cells.len())
+            .filter(|&index| {
+                return index != self.start
+                    && index != self.goal
+                    && !self.cells[index].blocked
+                    && distances[index] != UNREACHABLE;
+            })
+            .collect();
+        self.rng.shuffle(&mut free);
+        free.truncate(count);
+        self.coins = free;
+        self.coins_collected = 0;
+    }
+
+    /// Picks up the coin on the cell at `index`, if there is one. Returns
+    /// whether there was.
+    pub fn collect_coin(&mut self, index: usize) -> bool {
+        let Some(position) = self.coins.iter().position(|&coin| coin == index) else {
+            return false;
+        };
+        self.coins.swap_remove(position);
+        self.coins_collected += 1;
+        return true;
+    }
+
+    /// Puts back the wall between the adjacent cells `a` and `b`.
+    fn add_wall(&mut self, a: usize, b: usize) {
+        self.set_wall(a, b, true);
+    }
+
+    /// Removes the wall between `from` and `to`, leaving `current` on `from`.
+    fn carve(&mut self, from: usize, to: usize) {
+        self.current = from;
+        self.next = Some(to);
+        self.remove_wall(to);
+    }
+
+    /// The cell the generator is at, see `current`.
+    pub fn current_cell(&self) -> &Cell {
+        return &self.cells[self.current];
+    }
+
+    pub fn current_cell_mut(&mut self) -> &mut Cell {
+        return &mut self.cells[self.current];
+    }
+
+    fn visit(&mut self, index: usize) {
+        if !self.cells[index].visited {
+            self.cells[index].visited = true;
+            self.visited_count += 1;
+        }
+    }
+
+    fn update_current(&mut self) {
+        if self.history.len() == UNDO_DEPTH {
+            self.history.pop_front();
+        }
+        self.history.push_back(UndoStep {
+            current: self.current,
+            next: self.next,
+            visited: self.current_cell().visited,
+            carved: false,
+            last_direction: self.last_direction,
+            rng: self.rng.get_seed(),
+        });
+
+        self.next = self
+            .get_straight_neighbor()
+            .or_else(|| self.get_random_neighbor());
+
+        self.visit(self.current);
+        if let Some(next_index) = self.next {
+            self.history.back_mut().unwrap().carved = true;
+            self.stack.push_back(self.current);
+            self.remove_wall(next_index);
+            self.last_direction = (self.cells[next_index].layer == self.current_cell().layer)
+                .then(|| self.direction(self.current, next_index).normalize());
+            self.current = next_index;
+        } else {
+            self.last_direction = None;
+            if let Some(popped) = self.stack.pop_back() {
+                self.current = popped;
+            } else if let Some((from, to)) = self.cut_off_cell() {
+                // rooms can wall off parts of the grid from where the walk began
+                self.history.clear();
+                self.set_wall(from, to, false);
+                self.current = to;
+            } else {
+                self.finished = true;
+            }
+        }
+    }
+
+    /// An unvisited cell next to a visited one, with that visited one, if
+    /// there are rooms that could have kept the backtracker from it.
+    fn cut_off_cell(&self) -> Option<(usize, usize)> {
+        if self.rooms.is_empty() {
+            return None;
+        }
+        let cell = (0..self.cells.len()).find(|&index| {
+            return !self.cells[index].visited
+                && !self.cells[index].blocked
+                && self
+                    .neighbors(index)
+                    .iter()
+                    .any(|&neighbor| self.cells[neighbor].visited);
+        })?;
+        let visited: Neighbors = self
+            .neighbors(cell)
+            .into_iter()
+            .filter(|&neighbor| self.cells[neighbor].visited)
+            .collect();
+        return Some((visited[self.rng.usize(..visited.len())], cell));
+    }
+
+    /// Removes the walls between the cells from row `r0` and column `c0` to
+    /// row `r1` and column `c1` on the first layer, and marks them visited so
+    /// the recursive backtracker carves its corridors around the room. Once the
+    /// maze is finished, every room is connected to the rest of it. Not
+    /// possible on polar grids, whose rings have different lengths.
+    pub fn carve_room(&mut self, r0: usize, c0: usize, r1: usize, c1: usize) -> Result<(), String> {
+        if self.topology == Topology::Polar {
+            return Err("rooms can't be carved into polar grids".to_string());
+        }
+        self.checked_index(r0, c0)?;
+        self.checked_index(r1, c1)?;
+        if r0 > r1 || c0 > c1 {
+            return Err(format!(
+                "({}, {}) is below or right of ({}, {})",
+                r0, c0, r1, c1
+            ));
+        }
+
+        let cols = self.cols;
+        let blocked = (r0..=r1)
+            .flat_map(|row| (c0..=c1).map(move |col| row * cols + col))
+            .any(|index| self.cells[index].blocked);
+        if blocked {
+            return Err(format!(
+                "the room from ({}, {}) to ({}, {}) covers blocked cells",
+                r0, c0, r1, c1
+            ));
+        }
+
+        self.history.clear();
+        let inside = |cell: &Cell| -> bool {
+            return cell.layer == 0
+                && (r0..=r1).contains(&cell.row)
+                && (c0..=c1).contains(&cell.col);
+        };
+        for row in r0..=r1 {
+            for col in c0..=c1 {
+                let index = row * self.cols + col;
+                self.visit(index);
+                for neighbor in self.neighbors(index) {
+                    if inside(&self.cells[neighbor]) {
+                        self.set_wall(index, neighbor, false);
+                    }
+                }
+            }
+        }
+        self.rooms.push((r0, c0, r1, c1));
+        return Ok(());
+    }
+
+    /// Carves up to `count` rooms of random size at random spots with at least
+    /// one cell between them, see `carve_room`. Only the recursive backtracker
+    /// leaves visited cells alone, so this does nothing for other algorithms.
+    pub fn carve_rooms(&mut self, count: usize) {
+        if self.algorithm != GenAlgorithm::RecursiveBacktracker || self.topology == Topology::Polar
+        {
+            return;
+        }
+
+        for _ in 0..count {
+            for _ in 0..ROOM_ATTEMPTS {
+                let height = self.rng.usize(MIN_ROOM_SIZE..=MAX_ROOM_SIZE);
+                let width = self.rng.usize(MIN_ROOM_SIZE..=MAX_ROOM_SIZE);
+                if height > self.rows || width > self.cols {
+                    continue;
+                }
+                let r0 = self.rng.usize(..=self.rows - height);
+                let c0 = self.rng.usize(..=self.cols - width);
+                let (r1, c1) = (r0 + height - 1, c0 + width - 1);
+                let apart = self
+                    .rooms
+                    .iter()
+                    .all(|&(other_r0, other_c0, other_r1, other_c1)| {
+                        return r1 + 1 < other_r0
+                            || other_r1 + 1 < r0
+                            || c1 + 1 < other_c0
+                            || other_c1 + 1 < c0;
+                    });
+                if apart && self.carve_room(r0, c0, r1, c1).is_ok() {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Turns about a `density` share of the cells into swamps of a few cells
+    /// each, which cost `SWAMP_WEIGHT` to enter instead of one. The walls stay
+    /// the same.
+    pub fn add_terrain(&mut self, density: f32) {
+        let open: Vec<usize> = (0..self.cells.len())
+            .filter(|&index| !self.cells[index].blocked)
+            .collect();
+        let target = (open.len() as f32 * density.clamp(0.0, 1.0)).round() as usize;
+        let mut count = self.weights.iter().filter(|&&weight| weight > 1).count();
+        while count < target {
+            let size = self.rng.usize(MIN_SWAMP_SIZE..=MAX_SWAMP_SIZE);
+            let mut frontier = vec![open[self.rng.usize(..open.len())]];
+            let mut grown = 0;
+            while grown < size && count < target && !frontier.is_empty() {
+                let index = frontier.swap_remove(self.rng.usize(..frontier.len()));
+                if self.weights[index] > 1 || self.cells[index].blocked {
+                    continue;
+                }
+                self.weights[index] = SWAMP_WEIGHT;
+                grown += 1;
+                count += 1;
+                frontier.extend(self.neighbors(index));
+            }
+        }
+        self.clear_solution();
+    }
+
+    /// What entering the cell at `index` costs the Dijkstra solver.
+    pub fn weight(&self, index: usize) -> u32 {
+        return self.weights[index];
+    }
+
+    /// Blocks all but a `density` share of the cells, leaving one blob of
+    /// cells around where the generator began that grows in random directions
+    /// for the maze to be carved into. The start and the goal move to the
+    /// closest cell of the blob if they were blocked. Only the algorithms
+    /// that grow the maze from cell to cell can leave blocked cells alone, so
+    /// this does nothing for the others. Rooms have to be carved afterwards,
+    /// to keep clear of the blocked cells.
+    pub fn block_cells(&mut self, density: f32) {
+        if !self.algorithm.grows() || density >= 1.0 {
+            return;
+        }
+
+        let target = (self.cells.len() as f32 * density.max(0.0)).round() as usize;
+        // whatever the generator already started on
+        let started: Vec<usize> = (0..self.cells.len())
+            .filter(|&index| index == self.current || self.cells[index].visited)
+            .collect();
+        let mut carved = vec![false; self.cells.len()];
+        let mut count = started.len();
+        let mut frontier: Vec<usize> = Vec::new();
+        for &index in started.iter() {
+            carved[index] = true;
+            frontier.extend(self.neighbors(index));
+        }
+        while count < target && !frontier.is_empty() {
+            let index = frontier.swap_remove(self.rng.usize(..frontier.len()));
+            if carved[index] {
+                continue;
+            }
+            carved[index] = true;
+            count += 1;
+            frontier.extend(self.neighbors(index));
+        }
+
+        self.keep_cells(&carved);
+    }
+
+    /// Blocks the cells that aren't `carved`, and moves the start and the goal
+    /// to the closest carved cell if they were blocked.
+    fn keep_cells(&mut self, carved: &[bool]) {
+        for (cell, &carved) in self.cells.iter_mut().zip(carved.iter()) {
+            cell.blocked = !carved;
+        }
+        self.carved_count = carved.iter().filter(|&&carved| carved).count();
+        self.frontier.retain(|&index| carved[index]);
+        let closest = |target: usize| -> usize {
+            if carved[target] {
+                return target;
+            }
+            let distance = |index: &usize| self.center(*index).distance(self.center(target));
+            return (0..self.cells.len())
+                .filter(|&index| carved[index])
+                .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+                .unwrap();
+        };
+        (self.start, self.goal) = (closest(self.start), closest(self.goal));
+        self.player = self.start;
+    }
+
+    /// Opens walls between the part of the maze that can be reached from the
+    /// start and the rest, until every room can be reached.
+    fn connect_rooms(&mut self) {
+        self.history.clear();
+        loop {
+            let distances = self.distances_from(self.start);
+            let doors: Vec<(usize, usize)> = (0..self.cells.len())
+                .filter(|&index| distances[index] != UNREACHABLE)
+                .flat_map(|index| {
+                    return self
+                        .neighbors(index)
+                        .into_iter()
+                        .filter(|&neighbor| {
+                            return distances[neighbor] == UNREACHABLE
+                                && !self.cells[neighbor].blocked;
+                        })
+                        .map(move |neighbor| (index, neighbor));
+                })
+                .collect();
+            if doors.is_empty() {
+                return;
+            }
+            let (from, to) = doors[self.rng.usize(..doors.len())];
+            self.set_wall(from, to, false);
+        }
+    }
+
+    /// Takes back the latest step of the recursive backtracker, including
+    /// finishing the maze. Only the last `UNDO_DEPTH` steps are remembered;
+    /// beyond them, after braiding and before the backtracker continued in a
+    /// part of the grid cut off by rooms, this does nothing and returns `false`.
+    pub fn undo(&mut self) -> bool {
+        let Some(step) = self.history.pop_back() else {
+            return false;
+        };
+
+        if step.carved {
+            self.stack.pop_back();
+            self.set_wall(step.current, self.current, true);
+        } else if !self.finished {
+            self.stack.push_back(self.current);
+        }
+        if self.finished {
+            self.clear_solution();
+            self.finished = false;
+            // the doorways only open once the maze is done
+            self.set_border(self.start, false);
+            self.set_border(self.goal, false);
+        }
+        self.current = step.current;
+        self.next = step.next;
+        if self.current_cell().visited && !step.visited {
+            self.current_cell_mut().visited = false;
+            self.visited_count -= 1;
+        }
+        self.last_direction = step.last_direction;
+        self.rng.seed(step.rng);
+        return true;
+    }
+
+    fn add_frontier(&mut self, index: usize) {
+        for neighbor in self.neighbors(index) {
+            let cell = &self.cells[neighbor];
+            if !cell.visited && !cell.blocked && !self.frontier.contains(&neighbor) {
+                self.frontier.push(neighbor);
+            }
+        }
+    }
+
+    fn init_prim(&mut self) {
+        self.visit(self.current);
+        self.add_frontier(self.current);
+    }
+
+    /// Randomized Prim's algorithm: connect a random frontier cell to a random
+    /// visited neighbor, then grow the frontier around it.
+    fn update_prim(&mut self) {
+        if self.frontier.is_empty() {
+            self.finished = true;
+            return;
+        }
+
+        let cell = self
+            .frontier
+            .swap_remove(self.rng.usize(..self.frontier.len()));
+        let visited: Vec<usize> = self
+            .neighbors(cell)
+            .into_iter()
+            .filter(|&index| self.cells[index].visited)
+            .collect();
+
+        self.current = cell;
+        let next = visited[self.rng.usize(..visited.len())];
+        self.next = Some(next);
+        self.remove_wall(next);
+        self.visit(cell);
+        self.add_frontier(cell);
+
+        if self.frontier.is_empty() {
+            self.finished = true;
+        }
+    }
+
+    fn init_kruskal(&mut self) {
+        for index in 0..self.cells.len() {
+            for neighbor in self.neighbors(index) {
+                if index < neighbor {
+                    self.walls.push((index, neighbor));
+                }
+            }
+        }
+        self.rng.shuffle(&mut self.walls);
+        self.sets = DisjointSet::new(self.cells.len());
+    }
+
+    /// Randomized Kruskal's algorithm: remove shuffled walls until one joins two
+    /// different sets. `current` and `next` are the cells of the merged edge.
+    fn update_kruskal(&mut self) {
+        while let Some((a, b)) = self.walls.pop() {
+            if self.sets.union(a, b) {
+                self.current = a;
+                self.next = Some(b);
+                self.remove_wall(b);
+                self.visit(a);
+                self.visit(b);
+                break;
+            }
+        }
+
+        if self.walls.is_empty() {
+            self.finished = true;
+        }
+    }
+
+    fn init_wilson(&mut self) {
+        // the maze has to contain a cell before the first walk can end
+        self.visit(self.current);
+        self.frontier = (0..self.cells.len())
+            .filter(|&index| index != self.current)
+            .collect();
+        self.rng.shuffle(&mut self.frontier);
+    }
+
+    /// Wilson's algorithm: a loop-erased random walk from a cell outside of the
+    /// maze, carved into the maze as soon as it reaches it. `frontier` holds the
+    /// cells that may still be outside of the maze.
+    fn update_wilson(&mut self) {
+        self.erased.clear();
+
+        if self.walk.is_empty() {
+            while let Some(index) = self.frontier.pop() {
+                if !self.cells[index].visited {
+                    self.walk.push(index);
+                    self.current = index;
+                    self.next = None;
+                    return;
+                }
+            }
+            self.finished = true;
+            return;
+        }
+
+        let head = self.walk[self.walk.len() - 1];
+        let neighbors = self.neighbors(head);
+        let neighbor = neighbors[self.rng.usize(..neighbors.len())];
+
+        if self.cells[neighbor].visited {
+            self.walk.push(neighbor);
+            for i in 1..self.walk.len() {
+                self.carve(self.walk[i - 1], self.walk[i]);
+                self.visit(self.current);
+            }
+            self.walk.clear();
+            self.next = None;
+        } else if let Some(position) = self.walk.iter().position(|&index| index == neighbor) {
+            self.erased = self.walk.split_off(position + 1);
+        } else {
+            self.walk.push(neighbor);
+        }
+        self.current = neighbor;
+    }
+
+    fn init_aldous_broder(&mut self) {
+        self.visit(self.current);
+        self.remaining = self.cells.len() - 1;
+        self.finished = self.remaining == 0;
+    }
+
+    /// Aldous-Broder: move to a random neighbor, visited or not, and only carve
+    /// when entering a cell for the first time.
+    fn update_aldous_broder(&mut self) {
+        let neighbors = self.neighbors(self.current);
+        let neighbor = neighbors[self.rng.usize(..neighbors.len())];
+
+        if self.cells[neighbor].visited {
+            self.next = None;
+        } else {
+            self.next = Some(neighbor);
+            self.remove_wall(neighbor);
+            self.visit(neighbor);
+            self.remaining -= 1;
+        }
+        self.current = neighbor;
+        self.finished = self.remaining == 0;
+    }
+
+    fn init_eller(&mut self) {
+        self.row_sets = (0..self.cols).collect();
+        self.next_set = self.cols;
+        self.active_row = Some(0);
+    }
+
+    /// Eller's algorithm: processes one row per call, only keeping the set ids
+    /// of the current row in memory.
+    fn update_eller(&mut self) {
+        let row = match self.active_row {
+            Some(row) => row,
+            None => return,
+        };
+        let last_row = row + 1 == self.rows;
+        let first = row * self.cols;
+
+        // randomly join horizontal neighbors, always joining them on the last row
+        for col in 0..self.cols.saturating_sub(1) {
+            let (set, other) = (self.row_sets[col], self.row_sets[col + 1]);
+            if set != other && (last_row || self.rng.bool()) {
+                self.carve(first + col, first + col + 1);
+                for id in self.row_sets.iter_mut() {
+                    if *id == other {
+                        *id = set;
+                    }
+                }
+            }
+        }
+
+        for col in 0..self.cols {
+            self.visit(first + col);
+        }
+
+        if last_row {
+            self.active_row = None;
+            self.finished = true;
+            return;
+        }
+
+        // every set has to continue into the next row at least once
+        let mut next_sets: Vec<Option<usize>> = vec![None; self.cols];
+        let mut sets = self.row_sets.clone();
+        sets.sort_unstable();
+        sets.dedup();
+        for set in sets {
+            let cols: Vec<usize> = (0..self.cols)
+                .filter(|&col| self.row_sets[col] == set)
+                .collect();
+            let forced = cols[self.rng.usize(..cols.len())];
+            for col in cols {
+                if col == forced || self.rng.bool() {
+                    self.carve(first + col, first + self.cols + col);
+                    next_sets[col] = Some(set);
+                }
+            }
+        }
+
+        self.row_sets = next_sets
+            .into_iter()
+            .map(|set| {
+                return set.unwrap_or_else(|| {
+                    self.next_set += 1;
+                    return self.next_set - 1;
+                });
+            })
+            .collect();
+        self.current = first + self.cols;
+        self.next = None;
+        self.active_row = Some(row + 1);
+    }
+
+    fn init_recursive_division(&mut self) {
+        for index in 0..self.cells.len() {
+            for neighbor in self.neighbors(index) {
+                // the divisions only ever put back square walls inside the grid
+                let (cell, other) = (&self.cells[index], &self.cells[neighbor]);
+                let square = cell.row == other.row || cell.col == other.col;
+                if square && !self.crosses_seam(index, neighbor) {
+                    self.carve(index, neighbor);
+                }
+            }
+            self.visit(index);
+        }
+        self.next = None;
+        self.regions.push((0, 0, self.rows, self.cols));
+    }
+
+    /// Recursive division: splits the next region on the stack with a wall
+    /// containing a single gap. Regions are `(row, col, height, width)`.
+    fn update_recursive_division(&mut self) {
+        while let Some((row, col, height, width)) = self.regions.pop() {
+            if height < 2 || width < 2 {
+                continue;
+            }
+
+            let horizontal = if height == width {
+                self.rng.bool()
+            } else {
+                height > width
+            };
+
+            if horizontal {
+                // wall below `wall_row`, open at `gap`
+                let wall_row = row + self.rng.usize(..height - 1);
+                let gap = col + self.rng.usize(..width);
+                for c in col..col + width {
+                    if c != gap {
+                        self.add_wall(wall_row * self.cols + c, (wall_row + 1) * self.cols + c);
+                    }
+                }
+                self.current = wall_row * self.cols + gap;
+                self.regions.push((row, col, wall_row - row + 1, width));
+                self.regions
+                    .push((wall_row + 1, col, row + height - wall_row - 1, width));
+            } else {
+                // wall right of `wall_col`, open at `gap`
+                let wall_col = col + self.rng.usize(..width - 1);
+                let gap = row + self.rng.usize(..height);
+                for r in row..row + height {
+                    if r != gap {
+                        self.add_wall(r * self.cols + wall_col, r * self.cols + wall_col + 1);
+                    }
+                }
+                self.current = gap * self.cols + wall_col;
+                self.regions.push((row, col, height, wall_col - col + 1));
+                self.regions
+                    .push((row, wall_col + 1, height, col + width - wall_col - 1));
+            }
+            return;
+        }
+        self.finished = true;
+    }
+
+    /// Binary tree: carve either north or east from every cell, in row-major
+    /// order. Since the top row can only carve east and the rightmost column
+    /// only north, both always end up as straight corridors.
+    fn update_binary_tree(&mut self) {
+        let cell = self.current;
+        let (row, col) = (self.cells[cell].row, self.cells[cell].col);
+
+        let north = if row > 0 {
+            Some(cell - self.cols)
+        } else {
+            None
+        };
+        let east = if col + 1 < self.cols {
+            Some(cell + 1)
+        } else {
+            None
+        };
+        let target = match (north, east) {
+            (Some(north), Some(east)) => Some(if self.rng.bool() { north } else { east }),
+            (north, east) => north.or(east),
+        };
+
+        if let Some(target) = target {
+            self.carve(cell, target);
+        }
+        self.visit(cell);
+
+        if cell + 1 == self.cells.len() {
+            self.finished = true;
+        } else {
+            self.current = cell + 1;
+        }
+    }
+
+    /// Sidewinder: walk every row from left to right, either extending the
+    /// current run east or closing it by carving north from one of its cells.
+    /// The top row cannot carve north, so it becomes a single corridor.
+    fn update_sidewinder(&mut self) {
+        if self.run_closed {
+            self.run.clear();
+            self.run_closed = false;
+        }
+
+        let cell = self.current;
+        let (row, col) = (self.cells[cell].row, self.cells[cell].col);
+        self.run.push(cell);
+        self.visit(cell);
+
+        let east_edge = col + 1 == self.cols;
+        if east_edge || (row > 0 && self.rng.bool()) {
+            if row > 0 {
+                let member = self.run[self.rng.usize(..self.run.len())];
+                self.carve(member, member - self.cols);
+            }
+            self.run_closed = true;
+        } else {
+            self.carve(cell, cell + 1);
+        }
+
+        if cell + 1 == self.cells.len() {
+            self.run.clear();
+            self.finished = true;
+        } else {
+            self.current = cell + 1;
+        }
+    }
+
+    /// Hunt-and-kill: random walk until stuck, then scan one row per call for
+    /// an unvisited cell next to the maze and continue the walk from there.
+    /// The row is scanned on every layer at once.
+    fn update_hunt_and_kill(&mut self) {
+        if let Some(row) = self.hunt_row {
+            let cells: Vec<usize> = (0..self.layers)
+                .flat_map(|layer| self.row_cells(layer, row))
+                .collect();
+            for index in cells {
+                if self.cells[index].visited || self.cells[index].blocked {
+                    continue;
+                }
+
+                let visited: Vec<usize> = self
+                    .neighbors(index)
+                    .into_iter()
+                    .filter(|&neighbor| self.cells[neighbor].visited)
+                    .collect();
+                if !visited.is_empty() {
+                    self.carve(index, visited[self.rng.usize(..visited.len())]);
+                    self.visit(index);
+                    self.hunt_row = None;
+                    return;
+                }
+            }
+
+            if row + 1 == self.rows {
+                self.hunt_row = None;
+                self.finished = true;
+            } else {
+                self.hunt_row = Some(row + 1);
+            }
+            return;
+        }
+
+        if let Some(neighbor) = self.get_random_neighbor() {
+            self.carve(self.current, neighbor);
+            self.visit(neighbor);
+            self.current = neighbor;
+        } else {
+            self.next = None;
+            self.hunt_row = Some(0);
+        }
+    }
+
+    /// Growing tree: carve from a cell of the active list (`stack`) chosen by
+    /// `policy`, dropping cells from the list once they have no unvisited
+    /// neighbors left.
+    fn update_growing_tree(&mut self) {
+        if self.stack.is_empty() {
+            self.finished = true;
+            return;
+        }
+
+        let newest = self.stack.len() - 1;
+        let position = match self.policy {
+            CellPickPolicy::Newest => newest,
+            CellPickPolicy::Random => self.rng.usize(..self.stack.len()),
+            CellPickPolicy::Oldest => 0,
+            CellPickPolicy::MixNewestRandom(newest_chance) => {
+                if self.rng.f32() < newest_chance {
+                    newest
+                } else {
+                    self.rng.usize(..self.stack.len())
+                }
+            }
+        };
+
+        self.current = self.stack[position];
+        if let Some(neighbor) = self.get_random_neighbor() {
+            self.carve(self.current, neighbor);
+            self.visit(neighbor);
+            self.stack.push_back(neighbor);
+            self.current = neighbor;
+        } else {
+            self.next = None;
+            self.stack.remove(position);
+        }
+    }
+
+    /// Whether there is exactly one way between any two cells, which is what
+    /// every generator should leave behind: all cells can be reached and there
+    /// is one passage less than there are cells.
+    pub fn is_perfect(&self) -> bool {
+        let passages: usize = (0..self.cells.len())
+            .map(|index| {
+                return self
+                    .open_neighbors(index)
+                    .into_iter()
+                    .filter(|&neighbor| neighbor > index)
+                    .count();
+            })
+            .sum();
+        return self.is_connected() && passages + 1 == self.carved_len();
+    }
+
+    /// Whether `other` has cells of the same shape in the same places, with the
+    /// same walls. What the generator and the solvers are doing, like
+    /// `visited` and `current`, is left out on purpose, as are the start and
+    /// the goal, so two seeds can be compared while they're being carved.
+    pub fn structurally_eq(&self, other: &Grid) -> bool {
+        return self.shape() == other.shape()
+            && self
+                .cells
+                .iter()
+                .zip(other.cells.iter())
+                .all(|(a, b)| a.wall_bits() == b.wall_bits());
+    }
+
+    /// A hash of what `structurally_eq` compares, the same for mazes that are
+    /// structurally equal. It doesn't change between runs or platforms.
+    pub fn fingerprint(&self) -> u64 {
+        // FNV-1a
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let (topology, rows, cols, layers, wrap_x, wrap_y) = self.shape();
+        let shape = [topology as u64, rows as u64, cols as u64, layers as u64];
+        let wraps = [wrap_x as u64, wrap_y as u64];
+        let walls = self.cells.iter().map(|cell| cell.wall_bits() as u64);
+        for value in shape.into_iter().chain(wraps).chain(walls) {
+            for byte in value.to_le_bytes() {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+        return hash;
+    }
+
+    fn shape(&self) -> (Topology, usize, usize, usize, bool, bool) {
+        return (
+            self.topology,
+            self.rows,
+            self.cols,
+            self.layers,
+            self.wrap_x,
+            self.wrap_y,
+        );
+    }
+
+    /// Whether every cell that isn't blocked can be reached from every other
+    /// one. The end of a portal counts as reached when the other end is.
+    pub fn is_connected(&self) -> bool {
+        let distances = self.distances_from(self.start);
+        return (0..self.cells.len()).all(|index| {
+            let reached = |index: usize| distances[index] != UNREACHABLE;
+            return reached(index)
+                || self.cells[index].blocked
+                || self.portal_partner(index).is_some_and(reached);
+        });
+    }
+
+    /// How many cells aren't blocked.
+    pub fn carved_len(&self) -> usize {
+        return self.cells.iter().filter(|cell| !cell.blocked).count();
+    }
+
+    /// How far the generator got, from 0 to 1, by the share of the cells that
+    /// aren't blocked it visited.
+    pub fn progress(&self) -> f32 {
+        if self.finished {
+            return 1.0;
+        }
+        return self.visited_count as f32 / self.carved_count.max(1) as f32;
+    }
+
+    /// Whether the goal can be reached from the start through open passages.
+    /// Always true for perfect mazes, but not for ones that were loaded or
+    /// edited.
+    pub fn is_solvable(&self) -> bool {
+        let mut reached = vec![false; self.cells.len()];
+        reached[self.start] = true;
+        let mut queue = VecDeque::from([self.start]);
+        while let Some(index) = queue.pop_front() {
+            if index == self.goal {
+                return true;
+            }
+            for neighbor in self.moves(index) {
+                if !reached[neighbor] {
+                    reached[neighbor] = true;
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        return false;
+    }
+
+    pub fn dead_ends(&self) -> Vec<usize> {
+        return (0..self.cells.len())
+            .filter(|&index| self.open_neighbors(index).len() == 1)
+            .collect();
+    }
+
+    /// Counts the dead ends, junctions and straight corridors. Stairs count as
+    /// ways out, but never as part of a straight corridor, and in polar grids a
+    /// corridor only needs to be close to straight.
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats {
+            cells: self.carved_len(),
+            dead_ends: 0,
+            junctions: 0,
+            straights: 0,
+            visited: 0,
+        };
+        for (index, cell) in self.cells.iter().enumerate() {
+            let open = self.open_neighbors(index);
+            match open.len() {
+                1 => stats.dead_ends += 1,
+                2 => {
+                    let level = open.iter().all(|&n| self.cells[n].layer == cell.layer);
+                    let (a, b) = (
+                        self.direction(index, open[0]).normalize(),
+                        self.direction(index, open[1]).normalize(),
+                    );
+                    if level && a.dot(b) < -STRAIGHT_ALIGNMENT {
+                        stats.straights += 1;
+                    }
+                }
+                0 => {}
+                _ => stats.junctions += 1,
+            }
+            if cell.visited {
+                stats.visited += 1;
+            }
+        }
+        return stats;
+    }
+
+    /// A rating of how hard the maze is to solve from 0 to 100, or 0 if the
+    /// goal can't be reached. It adds up the share of the cells the solution
+    /// goes through, the share of the solution's cells that are junctions,
+    /// and twice the share of the cells that are dead ends off the solution,
+    /// each capped at one and weighted by `LENGTH_WEIGHT`, `JUNCTION_WEIGHT`
+    /// and `DEAD_END_WEIGHT`. Depends on nothing but the maze itself.
+    pub fn difficulty(&self) -> f32 {
+        let Some(path) = self.shortest_path() else {
+            return 0.0;
+        };
+
+        let mut on_path = vec![false; self.cells.len()];
+        for &index in path.iter() {
+            on_path[index] = true;
+        }
+        let junctions = path
+            .iter()
+            .filter(|&&index| self.open_neighbors(index).len() >= 3)
+            .count();
+        let dead_ends = (0..self.cells.len())
+            .filter(|&index| !on_path[index] && self.open_neighbors(index).len() == 1)
+            .count();
+
+        let cells = self.carved_len() as f32;
+        let length = path.len() as f32 / cells;
+        let junctions = junctions as f32 / path.len() as f32;
+        let dead_ends = (dead_ends as f32 * 2.0 / cells).min(1.0);
+        let score =
+            LENGTH_WEIGHT * length + JUNCTION_WEIGHT * junctions + DEAD_END_WEIGHT * dead_ends;
+        return (score * 100.0).clamp(0.0, 100.0);
+    }
+
+    /// Turns a `fraction` of the dead ends into loops by opening one more of
+    /// their interior walls, preferring neighbors that are dead ends as well.
+    pub fn braid(&mut self, fraction: f32) {
+        self.history.clear();
+        let mut dead_ends = self.dead_ends();
+        self.rng.shuffle(&mut dead_ends);
+        let count = (dead_ends.len() as f32 * fraction.clamp(0.0, 1.0)).round() as usize;
+
+        let current = self.current;
+        for &index in dead_ends.iter().take(count) {
+            // an earlier removal may already have opened this one
+            if self.open_neighbors(index).len() != 1 {
+                continue;
+            }
+
+            let closed: Vec<usize> = self
+                .neighbors(index)
+                .into_iter()
+                .filter(|&neighbor| {
+                    return !self.wall_open(index, neighbor) && !self.cells[neighbor].blocked;
+                })
+                .collect();
+            let preferred: Vec<usize> = closed
+                .iter()
+                .copied()
+                .filter(|&neighbor| self.open_neighbors(neighbor).len() == 1)
+                .collect();
+
+            let candidates = if preferred.is_empty() {
+                closed
+            } else {
+                preferred
+            };
+            if !candidates.is_empty() {
+                self.carve(index, candidates[self.rng.usize(..candidates.len())]);
+            }
+        }
+        self.current = current;
+        self.next = None;
+        self.solution = None;
+    }
+
+    /// The side a passage under `index` would start from, and the cells it
+    /// would connect, if `index` is a straight corridor that can become a
+    /// crossing.
+    fn crossing_ends(&self, index: usize) -> Option<(Direction, usize, usize)> {
+        if index == self.start || index == self.goal || self.cells[index].under.is_some() {
+            return None;
+        }
+        if self.open_neighbors(index).len() != 2 {
+            return None;
+        }
+        let across = |direction: Direction| -> bool {
+            return self
+                .beyond(index, direction)
+                .is_some_and(|neighbor| self.passage(index, neighbor) == Some(neighbor));
+        };
+        for (over, under) in [
+            (Direction::East, Direction::North),
+            (Direction::North, Direction::East),
+        ] {
+            if !across(over) || !across(over.opposite()) {
+                continue;
+            }
+            let ends = self
+                .beyond(index, under)
+                .zip(self.beyond(index, under.opposite()));
+            if let Some((a, b)) = ends {
+                let free = |cell: usize| -> bool {
+                    return self.cells[cell].under.is_none() && !self.cells[cell].blocked;
+                };
+                if a != b && free(a) && free(b) {
+                    return Some((under, a, b));
+                }
+            }
+        }
+        return None;
+    }
+
+    /// Turns a `fraction` of the straight corridors of a finished square maze
+    /// into crossings, with a passage running under the corridor between the
+    /// cells on either side. A wall goes back up somewhere on the old way
+    /// between those two cells, so a perfect maze stays perfect.
+    pub fn weave(&mut self, fraction: f32) {
+        if self.topology != Topology::Square {
+            return;
+        }
+        self.history.clear();
+        let mut straights: Vec<usize> = (0..self.cells.len())
+            .filter(|&index| self.crossing_ends(index).is_some())
+            .collect();
+        self.rng.shuffle(&mut straights);
+        let count = (straights.len() as f32 * fraction.clamp(0.0, 1.0)).round() as usize;
+
+        for &index in straights.iter().take(count) {
+            // an earlier crossing may have changed the corridors around this one
+            let Some((under, a, b)) = self.crossing_ends(index) else {
+                continue;
+            };
+            // walls only go up between neighbors, so not through portals
+            let Some(path) = self.path_between(a, b, Grid::open_neighbors) else {
+                continue;
+            };
+            // walls can't go up at crossings or between the ends of a passage
+            // under one
+            let closable: Vec<(usize, usize)> = path
+                .windows(2)
+                .map(|pair| (pair[0], pair[1]))
+                .filter(|&(from, to)| {
+                    return from != index
+                        && to != index
+                        && self.cells[from].under.is_none()
+                        && self.cells[to].under.is_none()
+                        && self.passage(from, to) == Some(to);
+                })
+                .collect();
+            if closable.is_empty() {
+                continue;
+            }
+
+            let (from, to) = closable[self.rng.usize(..closable.len())];
+            self.set_wall(from, to, true);
+            self.set_wall(index, a, false);
+            self.set_wall(index, b, false);
+            self.cells[index].under = Some(under);
+        }
+        self.solution = None;
+    }
+
+    pub fn save_json(&self, path: &str) -> std::io::Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer(file, &self.saved_grid())?;
+        return Ok(());
+    }
+
+    /// Loads a maze written by `save_json`. The loaded maze counts as finished.
+    pub fn load_json(path: &str) -> std::io::Result<Grid> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        let mut grid = Self::from_saved(serde_json::from_reader(file)?)?;
+        grid.finished = true;
+        return Ok(grid);
+    }
+
+    /// Saves the walls in the binary `.maze` format: the magic `MAZE`, a
+    /// version byte and the rows and cols as little-endian `u32`s, followed by
+    /// one nibble per cell, two cells to a byte with the first in the low
+    /// nibble, with a bit set for every closed wall from 1 for the top going
+    /// clockwise. Only square mazes with a single layer fit.
+    pub fn save_maze(&self, path: &str) -> std::io::Result<()> {
+        if self.topology != Topology::Square || self.layers != 1 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "only square mazes with a single layer can be saved as .maze",
+            ));
+        }
+        let mut bytes = Vec::with_capacity(MAZE_HEADER_LEN + self.cells.len().div_ceil(2));
+        bytes.extend_from_slice(MAZE_MAGIC);
+        bytes.push(MAZE_VERSION);
+        bytes.extend_from_slice(&(self.rows as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.cols as u32).to_le_bytes());
+        for pair in self.cells.chunks(2) {
+            // the four sides have the same bits in `Walls`
+            let nibbles = pair.iter().map(|cell| cell.walls.bits() & 0xf);
+            bytes.push(nibbles.rev().fold(0, |byte, nibble| byte << 4 | nibble));
+        }
+        return std::fs::write(path, bytes);
+    }
+
+    /// Loads a maze written by `save_maze`, with the start and goal in the
+    /// corners like `from_ascii`. The loaded maze counts as finished.
+    pub fn load_maze(path: &str) -> std::io::Result<Grid> {
+        let invalid = |message: &str| {
+            return std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string());
+        };
+        let bytes = std::fs::read(path)?;
+        if bytes.len() < MAZE_HEADER_LEN || &bytes[..4] != MAZE_MAGIC {
+            return Err(invalid("not a .maze file"));
+        }
+        if bytes[4] != MAZE_VERSION {
+            return Err(invalid(&format!("unknown .maze version {}", bytes[4])));
+        }
+        let rows = u32::from_le_bytes(bytes[5..9].try_into().unwrap()) as usize;
+        let cols = u32::from_le_bytes(bytes[9..13].try_into().unwrap()) as usize;
+        let size = rows.checked_mul(cols);
+        let expected = size.and_then(|size| size.div_ceil(2).checked_add(MAZE_HEADER_LEN));
+        if rows == 0 || cols == 0 || expected != Some(bytes.len()) {
+            return Err(invalid(
+                "the size of the file doesn't match its rows and cols",
+            ));
+        }
+
+        let mut grid = Self::blank(
+            rows,
+            cols,
+            1,
+            Topology::Square,
+            GenAlgorithm::RecursiveBacktracker,
+            0,
+        );
+        for (index, cell) in grid.cells.iter_mut().enumerate() {
+            let nibble = bytes[MAZE_HEADER_LEN + index / 2] >> (index % 2 * 4);
+            cell.walls = Walls::from_bits(cell.walls.bits() & !0xf | nibble & 0xf);
+            cell.visited = true;
+        }
+        grid.visited_count = grid.cells.len();
+        grid.finished = true;
+        return Ok(grid);
+    }
+
+    /// Saves the maze together with the state of its generator, so that
+    /// `load_state` can resume an unfinished maze step for step. Solvers and
+    /// play mode aren't saved.
+    pub fn save_state(&self, path: &str) -> std::io::Result<()> {
+        let saved = SavedState {
+            grid: self.saved_grid(),
+            seed: self.seed,
+            rng: self.rng.get_seed(),
+            algorithm: self.algorithm,
+            policy: self.policy,
+            straightness: self.straightness,
+            last_direction: self.last_direction.map(|direction| direction.to_array()),
+            finished: self.finished,
+            stack: self.stack.clone(),
+            current: self.current,
+            next: self.next,
+            frontier: self.frontier.clone(),
+            walls: self.walls.clone(),
+            sets: DisjointSet {
+                parent: self.sets.parent.clone(),
+                rank: self.sets.rank.clone(),
+            },
+            walk: self.walk.clone(),
+            erased: self.erased.clone(),
+            remaining: self.remaining,
+            row_sets: self.row_sets.clone(),
+            next_set: self.next_set,
+            active_row: self.active_row,
+            regions: self.regions.clone(),
+            run: self.run.clone(),
+            run_closed: self.run_closed,
+            hunt_row: self.hunt_row,
+            rooms: self.rooms.clone(),
+        };
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer(file, &saved)?;
+        return Ok(());
+    }
+
+    /// Loads a maze written by `save_state`, to carry on generating it.
+    pub fn load_state(path: &str) -> std::io::Result<Grid> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        let saved: SavedState = serde_json::from_reader(file)?;
+        let mut grid = Self::from_saved(saved.grid)?;
+
+        let size = grid.cells.len();
+        let out_of_range = saved
+            .stack
+            .iter()
+            .chain(saved.next.iter())
+            .chain(saved.frontier.iter())
+            .chain(saved.walls.iter().flat_map(|(a, b)| [a, b]))
+            .chain(saved.walk.iter())
+            .chain(saved.erased.iter())
+            .chain(saved.run.iter())
+            .chain(saved.sets.parent.iter())
+            .any(|&index| index >= size);
+        let sets_len = saved.sets.parent.len();
+        if saved.current >= size
+            || out_of_range
+            || (sets_len != 0 && sets_len != size)
+            || saved.sets.rank.len() != sets_len
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "the generator state doesn't match the grid dimensions",
+            ));
+        }
+
+        grid.seed = saved.seed;
+        grid.rng = fastrand::Rng::with_seed(saved.rng);
+        grid.algorithm = saved.algorithm;
+        grid.policy = saved.policy;
+        grid.straightness = saved.straightness;
+        grid.last_direction = saved.last_direction.map(Vec2::from_array);
+        grid.finished = saved.finished;
+        grid.stack = saved.stack;
+        grid.current = saved.current;
+        grid.next = saved.next;
+        grid.frontier = saved.frontier;
+        grid.walls = saved.walls;
+        grid.sets = saved.sets;
+        grid.walk = saved.walk;
+        grid.erased = saved.erased;
+        grid.remaining = saved.remaining;
+        grid.row_sets = saved.row_sets;
+        grid.next_set = saved.next_set;
+        grid.active_row = saved.active_row;
+        grid.regions = saved.regions;
+        grid.run = saved.run;
+        grid.run_closed = saved.run_closed;
+        grid.hunt_row = saved.hunt_row;
+        grid.rooms = saved.rooms;
+        return Ok(grid);
+    }
+
+    fn saved_grid(&self) -> SavedGrid {
+        return SavedGrid {
+            rows: self.rows,
+            cols: self.cols,
+            cells: self.cells.clone(),
+            start: self.start,
+            goal: self.goal,
+            topology: self.topology,
+            layers: self.layers,
+            wrap_x: self.wrap_x,
+            wrap_y: self.wrap_y,
+            portals: self.portals.clone(),
+            weights: self.weights.clone(),
+        };
+    }
+
+    /// A grid with the walls of a saved maze, with the generator not started.
+    fn from_saved(saved: SavedGrid) -> std::io::Result<Grid> {
+        let invalid = std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "cells, start or goal don't match the grid dimensions",
+        );
+        if saved.rows == 0 || saved.cols == 0 || saved.layers == 0 {
+            return Err(invalid);
+        }
+
+        let mut grid = Self::blank(
+            saved.rows,
+            saved.cols,
+            saved.layers,
+            saved.topology,
+            GenAlgorithm::RecursiveBacktracker,
+            0,
+        );
+        let size = grid.cells.len();
+        let portals_fit = saved.portals.iter().all(|&(a, b)| a < size && b < size);
+        let weights_fit = saved.weights.is_empty() || saved.weights.len() == size;
+        if saved.cells.len() != size
+            || saved.start >= size
+            || saved.goal >= size
+            || !portals_fit
+            || !weights_fit
+        {
+            return Err(invalid);
+        }
+        grid.cells = saved.cells;
+        grid.portals = saved.portals;
+        if !saved.weights.is_empty() {
+            grid.weights = saved.weights;
+        }
+        grid.visited_count = grid.cells.iter().filter(|cell| cell.visited).count();
+        grid.carved_count = grid.carved_len();
+        (grid.wrap_x, grid.wrap_y) = (saved.wrap_x, saved.wrap_y);
+        grid.start = saved.start;
+        grid.goal = saved.goal;
+        grid.player = saved.start;
+        return Ok(grid);
+    }
+
+    /// For every cell, the cells it connects to through an open wall or a
+    /// passage under a crossing, whatever the shape of the cells.
+    pub fn to_adjacency(&self) -> Vec<Vec<usize>> {
+        return (0..self.cells.len())
+            .map(|index| self.open_neighbors(index).into_iter().collect())
+            .collect();
+    }
+
+    /// The passages of `to_adjacency` as text, one `a b` line per passage,
+    /// with the smaller index first.
+    pub fn to_edge_list(&self) -> String {
+        let mut edges = String::new();
+        for (a, neighbors) in self.to_adjacency().into_iter().enumerate() {
+            for b in neighbors.into_iter().filter(|&b| b > a) {
+                edges += &format!("{} {}\n", a, b);
+            }
+        }
+        return edges;
+    }
+
+    /// The passages of `to_adjacency` as an undirected Graphviz graph, with
+    /// the start and the goal highlighted.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("graph maze {\n");
+        dot += &format!("    {} [color=green];\n", self.start);
+        dot += &format!("    {} [color=red];\n", self.goal);
+        for (a, neighbors) in self.to_adjacency().into_iter().enumerate() {
+            for b in neighbors.into_iter().filter(|&b| b > a) {
+                dot += &format!("    {} -- {};\n", a, b);
+            }
+        }
+        dot += "}\n";
+        return dot;
+    }
+
+    /// Rust source for a `const` array called `name` with a row of bytes for
+    /// every row of the bottom layer. Each byte has a bit set for every open
+    /// wall of its cell: north, east, south, west and, on hex grids, north-east
+    /// and south-west, from the lowest bit up. The rings of a polar grid get
+    /// longer further out, so the shorter ones are padded with 0 up to the
+    /// length of the outermost ring.
+    pub fn to_rust_source(&self, name: &str) -> String {
+        let width = (0..self.rows)
+            .map(|row| self.row_len(row))
+            .max()
+            .unwrap_or(0);
+        let mut source = String::from(
+            "/// Open walls: 1 north, 2 east, 4 south, 8 west, 16 north-east, 32 south-west.\n",
+        );
+        source += &format!("const {}: [[u8; {}]; {}] = [\n", name, width, self.rows);
+        for row in 0..self.rows {
+            let cells = self.row_cells(0, row).map(|index| {
+                // the six sides have the same bits in `Walls`
+                return !self.cells[index].walls.bits() & 0x3f;
+            });
+            let padding = std::iter::repeat_n(0, width - self.row_len(row));
+            let bytes: Vec<String> = cells.chain(padding).map(|open| open.to_string()).collect();
+            source += &format!("    [{}],\n", bytes.join(", "));
+        }
+        source += "];\n";
+        return source;
+    }
+
+    /// Renders the maze as text, with `+` corners, `---` and `|` walls and
+    /// three spaces per cell. Shared walls are only drawn once. Only square
+    /// mazes with a single layer fit, anything else is an error.
+    pub fn to_ascii(&self) -> Result<String, String> {
+        if self.topology != Topology::Square || self.layers != 1 {
+            return Err("only square mazes with a single layer can be written as text".to_string());
+        }
+        let mut ascii = String::new();
+
+        for row in 0..self.rows {
+            let cells = &self.cells[row * self.cols..(row + 1) * self.cols];
+
+            for cell in cells {
+                ascii += if cell.walls.contains(Walls::TOP) {
+                    "+---"
+                } else {
+                    "+   "
+                };
+            }
+            ascii += "+\n";
+
+            for cell in cells {
+                ascii += if cell.walls.contains(Walls::LEFT) {
+                    "|   "
+                } else {
+                    "    "
+                };
+            }
+            ascii += if cells[self.cols - 1].walls.contains(Walls::RIGHT) {
+                "|\n"
+            } else {
+                " \n"
+            };
+        }
+
+        for cell in &self.cells[(self.rows - 1) * self.cols..] {
+            ascii += if cell.walls.contains(Walls::BOTTOM) {
+                "+---"
+            } else {
+                "+   "
+            };
+        }
+        ascii += "+\n";
+        return Ok(ascii);
+    }
+
+    /// Renders the maze like `to_ascii`, but with box-drawing characters for
+    /// the walls. Cells the generator hasn't reached yet are shaded and the
+    /// one it's at is marked; once the maze is finished, the start and goal
+    /// are marked instead. Like `to_ascii`, it's an error for mazes that
+    /// aren't square or have more than one layer.
+    pub fn to_box_drawing(&self) -> Result<String, String> {
+        if self.topology != Topology::Square || self.layers != 1 {
+            return Err("only square mazes with a single layer can be drawn as text".to_string());
+        }
+        // indexed by the walls meeting at a corner: 1 up, 2 right, 4 down, 8 left
+        const CORNERS: [char; 16] = [
+            ' ', '╵', '╶', '└', '╷', '│', '┌', '├', '╴', '┘', '─', '┴', '┐', '┤', '┬', '┼',
+        ];
+        let (rows, cols) = (self.rows, self.cols);
+        // the wall above or left of a cell, or below or right of the last one
+        let horizontal = |row: usize, col: usize| -> bool {
+            if row < rows {
+                return self.cells[row * cols + col].walls.contains(Walls::TOP);
+            }
+            return self.cells[(row - 1) * cols + col]
+                .walls
+                .contains(Walls::BOTTOM);
+        };
+        let vertical = |row: usize, col: usize| -> bool {
+            if col < cols {
+                return self.cells[row * cols + col].walls.contains(Walls::LEFT);
+            }
+            return self.cells[row * cols + col - 1]
+                .walls
+                .contains(Walls::RIGHT);
+        };
+
+        let mut text = String::new();
+        for row in 0..=rows {
+            for col in 0..=cols {
+                let up = row > 0 && vertical(row - 1, col);
+                let right = col < cols && horizontal(row, col);
+                let down = row < rows && vertical(row, col);
+                let left = col > 0 && horizontal(row, col - 1);
+                let walls = up as usize | (right as usize) << 1 | (down as usize) << 2;
+                text.push(CORNERS[walls | (left as usize) << 3]);
+                if col < cols {
+                    text += if right { "───" } else { "   " };
+                }
+            }
+            text.push('\n');
+            if row == rows {
+                break;
+            }
+
+            for col in 0..=cols {
+                text.push(if vertical(row, col) { '│' } else { ' ' });
+                if col == cols {
+                    break;
+                }
+                let index = row * cols + col;
+                let cell = &self.cells[index];
+                text += if cell.blocked {
+                    "███"
+                } else if !self.finished && index == self.current {
+                    " ● "
+                } else if !cell.visited {
+                    "░░░"
+                } else if self.finished && index == self.start {
+                    " S "
+                } else if self.finished && index == self.goal {
+                    " G "
+                } else {
+                    "   "
+                };
+            }
+            text.push('\n');
+        }
+        return Ok(text);
+    }
+
+    /// Forgets the solution, or how far a solver got.
+    fn clear_solution(&mut self) {
+        self.solver = None;
+        self.queue.clear();
+        self.came_from.clear();
+        self.open.clear();
+        self.g_scores.clear();
+        self.closed.clear();
+        self.trail.clear();
+        self.solution = None;
+        self.solve_failed = false;
+        self.auto_route.clear();
+        for cell in self.cells.iter_mut() {
+            cell.filled = false;
+        }
+    }
+
+    /// Starts solving the finished maze from `start` to `goal`.
+    pub fn solve(&mut self, algorithm: SolveAlgorithm) {
+        self.clear_solution();
+        self.solver = Some(algorithm);
+        self.came_from.insert(self.start, self.start);
+        match algorithm {
+            SolveAlgorithm::Bfs => self.queue.push_back(self.start),
+            SolveAlgorithm::Dijkstra | SolveAlgorithm::DeadEndFilling => {}
+            SolveAlgorithm::WallFollower => {
+                self.follower = self.start;
+                self.trail.push(self.start);
+            }
+            SolveAlgorithm::AStar => {
+                self.g_scores = vec![u32::MAX; self.cells.len()];
+                self.g_scores[self.start] = 0;
+                self.closed = vec![false; self.cells.len()];
+                self.open
+                    .push(Reverse((self.heuristic(self.start), self.start)));
+            }
+        }
+    }
+
+    /// Advances the running solver by a single step.
+    pub fn solve_step(&mut self) {
+        match self.solver {
+            Some(SolveAlgorithm::Bfs) => self.solve_bfs(),
+            Some(SolveAlgorithm::AStar) => self.solve_astar(),
+            Some(SolveAlgorithm::DeadEndFilling) => self.solve_dead_end_filling(),
+            Some(SolveAlgorithm::WallFollower) => self.solve_wall_follower(),
+            Some(SolveAlgorithm::Dijkstra) | None => {}
+        }
+    }
+
+    /// Follows `came_from` back from `goal` and stores the path as the solution.
+    fn reconstruct_solution(&mut self) {
+        let mut path = vec![self.goal];
+        let mut index = self.goal;
+        while index != self.start {
+            index = self.came_from[&index];
+            path.push(index);
+        }
+        path.reverse();
+        self.solution = Some(path);
+    }
+
+    /// Breadth-first search: expands the oldest cell of `queue` through its
+    /// open walls.
+    fn solve_bfs(&mut self) {
+        let cell = match self.queue.pop_front() {
+            Some(cell) => cell,
+            None => {
+                self.solver = None;
+                self.solve_failed = true;
+                return;
+            }
+        };
+
+        if cell == self.goal {
+            self.reconstruct_solution();
+            self.queue.clear();
+            self.solver = None;
+            return;
+        }
+
+        for neighbor in self.moves(cell) {
+            if let Entry::Vacant(entry) = self.came_from.entry(neighbor) {
+                entry.insert(cell);
+                self.queue.push_back(neighbor);
+            }
+        }
+    }
+
+    /// The fewest `moves` from `source` to every cell, or `UNREACHABLE` for
+    /// the cells there is no way to. The ends of portals can't be stood on,
+    /// so they are unreachable as well.
+    pub fn distances_from(&self, source: usize) -> Vec<u32> {
+        let mut distances = vec![UNREACHABLE; self.cells.len()];
+        distances[source] = 0;
+        let mut queue = VecDeque::from([source]);
+        while let Some(index) = queue.pop_front() {
+            for neighbor in self.moves(index) {
+                if distances[neighbor] == UNREACHABLE {
+                    distances[neighbor] = distances[index] + 1;
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        return distances;
+    }
+
+    /// Like `distances_from`, but adds up the weights of the cells entered on
+    /// the cheapest way instead of counting steps.
+    pub fn costs_from(&self, source: usize) -> Vec<u32> {
+        let mut costs = vec![UNREACHABLE; self.cells.len()];
+        costs[source] = 0;
+        let mut open = BinaryHeap::from([Reverse((0, source))]);
+        while let Some(Reverse((cost, index))) = open.pop() {
+            if cost > costs[index] {
+                continue;
+            }
+            for neighbor in self.moves(index) {
+                let neighbor_cost = cost + self.weights[neighbor];
+                if neighbor_cost < costs[neighbor] {
+                    costs[neighbor] = neighbor_cost;
+                    open.push(Reverse((neighbor_cost, neighbor)));
+                }
+            }
+        }
+        return costs;
+    }
+
+    /// Finds the way from `start` to `goal` with the fewest moves, portals
+    /// included, for `auto_solve_step` to walk along from the start. Returns
+    /// whether there is a way.
+    pub fn auto_solve_start(&mut self) -> bool {
+        let mut came_from = vec![None; self.cells.len()];
+        came_from[self.start] = Some(self.start);
+        let mut queue = VecDeque::from([self.start]);
+        while let Some(index) = queue.pop_front() {
+            if index == self.goal {
+                break;
+            }
+            for neighbor in self.moves(index) {
+                if came_from[neighbor].is_none() {
+                    came_from[neighbor] = Some(index);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        self.auto_route.clear();
+        self.auto_position = 0;
+        if came_from[self.goal].is_none() {
+            return false;
+        }
+        let mut index = self.goal;
+        self.auto_route.push(index);
+        while index != self.start {
+            index = came_from[index].unwrap();
+            self.auto_route.push(index);
+        }
+        self.auto_route.reverse();
+        return true;
+    }
+
+    /// Moves one cell further along the way found by `auto_solve_start`.
+    /// Returns whether there was a cell left to move to.
+    pub fn auto_solve_step(&mut self) -> bool {
+        if self.auto_position + 1 >= self.auto_route.len() {
+            return false;
+        }
+        self.auto_position += 1;
+        return true;
+    }
+
+    /// The cell `auto_solve_step` has got to, if it's walking a way.
+    pub fn auto_solver(&self) -> Option<usize> {
+        return self.auto_route.get(self.auto_position).copied();
+    }
+
+    /// One of the paths with the fewest steps from `start` to `goal`, without
+    /// running a solver, or `None` if there is no way through.
+    pub fn shortest_path(&self) -> Option<Vec<usize>> {
+        return self.path_between(self.start, self.goal, Grid::moves);
+    }
+
+    /// One of the ways from `from` to `to` with the fewest steps, taking the
+    /// steps `neighbors` gives for every cell, or `None` if there is no way.
+    fn path_between(
+        &self,
+        from: usize,
+        to: usize,
+        neighbors: fn(&Grid, usize) -> Neighbors,
+    ) -> Option<Vec<usize>> {
+        // searched from `from`, since a portal only leads one way from a cell
+        let mut came_from = vec![None; self.cells.len()];
+        came_from[from] = Some(from);
+        let mut queue = VecDeque::from([from]);
+        while let Some(index) = queue.pop_front() {
+            if index == to {
+                break;
+            }
+            for neighbor in neighbors(self, index) {
+                if came_from[neighbor].is_none() {
+                    came_from[neighbor] = Some(index);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        let mut index = to;
+        let mut path = vec![index];
+        while index != from {
+            index = came_from[index]?;
+            path.push(index);
+        }
+        path.reverse();
+        return Some(path);
+    }
+
+    /// The longest of the shortest paths between two cells that can reach
+    /// `start`, found by walking as far as possible from `start` and then as
+    /// far as possible from there. On a perfect maze that is the longest path
+    /// there is; on a braided one it is only the longest from where the first
+    /// walk ended, which can be shorter than the true longest.
+    pub fn longest_path(&self) -> Vec<usize> {
+        let farthest = |distances: &[u32]| -> usize {
+            return (0..distances.len())
+                .filter(|&index| distances[index] != UNREACHABLE)
+                .max_by_key(|&index| distances[index])
+                .unwrap_or(self.start);
+        };
+        let first = farthest(&self.distances_from(self.start));
+        let last = farthest(&self.distances_from(first));
+        return self
+            .path_between(first, last, Grid::moves)
+            .unwrap_or_else(|| vec![last]);
+    }
+
+    /// At most the fewest steps from `index` to `goal` if there were no walls,
+    /// which is exactly the Manhattan distance on square grids.
+    fn grid_distance(&self, index: usize) -> u32 {
+        let (cell, goal) = (&self.cells[index], &self.cells[self.goal]);
+        let distance = |rows: i32, cols: i32| -> u32 {
+            return match self.topology {
+                // every step on a triangle grid changes either the row or the column
+                Topology::Square | Topology::Triangle => rows.unsigned_abs() + cols.unsigned_abs(),
+                Topology::Hex => (rows.abs() + cols.abs() + (rows + cols).abs()) as u32 / 2,
+                // every step changes the ring by at most one
+                Topology::Polar => rows.unsigned_abs(),
+            };
+        };
+
+        // the goal as seen across every wrapping edge as well
+        let shifts = |wrap: bool, size: usize| -> Vec<i32> {
+            if wrap {
+                return vec![-(size as i32), 0, size as i32];
+            }
+            return vec![0];
+        };
+        let rows = goal.row as i32 - cell.row as i32;
+        let cols = goal.col as i32 - cell.col as i32;
+        let mut closest = u32::MAX;
+        for row_shift in shifts(self.wrap_y, self.rows) {
+            for col_shift in shifts(self.wrap_x, self.cols) {
+                closest = closest.min(distance(rows + row_shift, cols + col_shift));
+            }
+        }
+        return cell.layer.abs_diff(goal.layer) as u32 + closest;
+    }
+
+    /// The `h` of A*: `grid_distance`, or 0 once there are portals, since a
+    /// portal can be a shortcut that the distance on the grid knows nothing
+    /// about. With 0 A* is just as right, only slower.
+    fn heuristic(&self, index: usize) -> u32 {
+        if !self.portals.is_empty() {
+            return 0;
+        }
+        return self.grid_distance(index);
+    }
+
+    /// A*: expands the open cell with the lowest `g + h`, using `heuristic`
+    /// as `h`.
+    fn solve_astar(&mut self) {
+        let cell = loop {
+            match self.open.pop() {
+                Some(Reverse((_, cell))) if self.closed[cell] => continue,
+                Some(Reverse((_, cell))) => break cell,
+                None => {
+                    self.solver = None;
+                    self.solve_failed = true;
+                    return;
+                }
+            }
+        };
+        self.closed[cell] = true;
+
+        if cell == self.goal {
+            self.reconstruct_solution();
+            self.open.clear();
+            self.solver = None;
+            return;
+        }
+
+        let g_score = self.g_scores[cell] + 1;
+        for neighbor in self.moves(cell) {
+            if g_score < self.g_scores[neighbor] {
+                self.g_scores[neighbor] = g_score;
+                self.came_from.insert(neighbor, cell);
+                self.open
+                    .push(Reverse((g_score + self.heuristic(neighbor), neighbor)));
+            }
+        }
+    }
+
+    /// Dijkstra: computes the cheapest path from `start` to `goal`, where
+    /// entering a cell costs its weight. Returns the total cost and the path,
+    /// which is also stored as the solution.
+    pub fn solve_dijkstra(&mut self) -> Option<(u32, Vec<usize>)> {
+        self.solve(SolveAlgorithm::Dijkstra);
+        self.g_scores = vec![u32::MAX; self.cells.len()];
+        self.g_scores[self.start] = 0;
+        self.closed = vec![false; self.cells.len()];
+        self.open.push(Reverse((0, self.start)));
+
+        while let Some(Reverse((cost, cell))) = self.open.pop() {
+            if self.closed[cell] {
+                continue;
+            }
+            self.closed[cell] = true;
+            if cell == self.goal {
+                break;
+            }
+
+            for neighbor in self.moves(cell) {
+                let neighbor_cost = cost + self.weights[neighbor];
+                if neighbor_cost < self.g_scores[neighbor] {
+                    self.g_scores[neighbor] = neighbor_cost;
+                    self.came_from.insert(neighbor, cell);
+                    self.open.push(Reverse((neighbor_cost, neighbor)));
+                }
+            }
+        }
+        self.open.clear();
+        self.solver = None;
+
+        if !self.closed[self.goal] {
+            self.solve_failed = true;
+            return None;
+        }
+        self.reconstruct_solution();
+        return Some((self.g_scores[self.goal], self.solution.clone()?));
+    }
+
+    /// Open neighbors of `index` that haven't been filled in.
+    fn unfilled_neighbors(&self, index: usize) -> Neighbors {
+        return self
+            .open_neighbors(index)
+            .into_iter()
+            .filter(|&neighbor| !self.cells[neighbor].filled)
+            .collect();
+    }
+
+    /// Dead-end filling: fills every dead end other than `start` and `goal` in
+    /// one pass per call. Once no dead ends are left, the unfilled cells of a
+    /// perfect maze are exactly the path from `start` to `goal`.
+    fn solve_dead_end_filling(&mut self) {
+        let dead_ends: Vec<usize> = (0..self.cells.len())
+            .filter(|&index| {
+                return index != self.start
+                    && index != self.goal
+                    && !self.cells[index].filled
+                    && self.unfilled_neighbors(index).len() <= 1;
+            })
+            .collect();
+
+        if !dead_ends.is_empty() {
+            for index in dead_ends {
+                self.cells[index].filled = true;
+            }
+            return;
+        }
+
+        self.solver = None;
+        // with loops the remaining cells aren't a single corridor, so give up
+        // instead of walking in circles
+        let mut path = vec![self.start];
+        while path[path.len() - 1] != self.goal && path.len() <= self.cells.len() {
+            let last = path[path.len() - 1];
+            let next = self
+                .unfilled_neighbors(last)
+                .into_iter()
+                .find(|&neighbor| path.len() < 2 || neighbor != path[path.len() - 2]);
+            match next {
+                Some(next) => path.push(next),
+                None => return,
+            }
+        }
+        if path[path.len() - 1] == self.goal {
+            self.solution = Some(path);
+        } else {
+            self.solve_failed = true;
+        }
+    }
+
+    /// Wall follower: walks from `start` keeping its right hand on the wall,
+    /// one step per call. Gives up after visiting every side of every cell,
+    /// which can only happen if `goal` isn't reachable along the walls.
+    fn solve_wall_follower(&mut self) {
+        if self.follower == self.goal {
+            // erase the dead ends and loops from the trail
+            let mut path: Vec<usize> = Vec::new();
+            for &index in self.trail.iter() {
+                if let Some(position) = path.iter().position(|&cell| cell == index) {
+                    path.truncate(position + 1);
+                } else {
+                    path.push(index);
+                }
+            }
+            self.solution = Some(path);
+            self.solver = None;
+            return;
+        }
+
+        if self.trail.len() > self.topology.sides() * self.cells.len() {
+            self.solver = None;
+            self.solve_failed = true;
+            return;
+        }
+
+        // the side the follower came in through, as if it had entered `start`
+        // from the south
+        let neighbors = self.neighbors(self.follower);
+        let directions = neighbors.len();
+        let back = match self.trail.len() {
+            0 | 1 => directions / 2,
+            len => {
+                let previous = self.trail[len - 2];
+                neighbors
+                    .iter()
+                    .position(|&cell| self.passage(self.follower, cell) == Some(previous))
+                    .unwrap()
+            }
+        };
+
+        // counterclockwise from the side it came in through, which tries the
+        // sharpest right turn first and turning back last
+        for turn in 1..=directions {
+            let neighbor = neighbors[(back + directions - turn) % directions];
+            if let Some(cell) = self.passage(self.follower, neighbor) {
+                self.follower = cell;
+                self.trail.push(cell);
+                return;
+            }
+        }
+
+        // the start is walled in on all sides
+        self.solver = None;
+        self.solve_failed = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn every_algorithm_makes_a_perfect_maze() {
+        let mut algorithm = GenAlgorithm::RecursiveBacktracker;
+        loop {
+            for seed in [1, 42, 1234] {
+                let mut grid = Grid::with_seed(20, 20, algorithm, seed);
+                grid.generate_full();
+                assert!(grid.is_perfect(), "{} with seed {}", algorithm.name(), seed);
+            }
+            algorithm = algorithm.cycle();
+            if algorithm == GenAlgorithm::RecursiveBacktracker {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn index_is_row_major() {
+        assert_eq!(index(3, 2, 5, 8), Some(26));
+        assert_eq!(index(0, 0, 5, 8), Some(0));
+        assert_eq!(index(4, 7, 5, 8), Some(39));
+        assert_eq!(index(5, 0, 5, 8), None);
+        assert_eq!(index(0, 8, 5, 8), None);
+        assert_eq!(index(-1, 0, 5, 8), None);
+    }
+
+    #[test]
+    fn same_seed_makes_the_same_maze() {
+        let walls = |grid: &Grid| -> Vec<Walls> {
+            return grid.cells.iter().map(|cell| cell.walls).collect();
+        };
+        let mut a = Grid::new_seeded(15, 12, 7);
+        let mut b = Grid::new_seeded(15, 12, 7);
+        a.generate_full();
+        b.generate_full();
+        assert_eq!(walls(&a), walls(&b));
+
+        let mut a = Grid::with_seed(15, 12, GenAlgorithm::Wilson, 7);
+        let mut b = Grid::with_seed(15, 12, GenAlgorithm::Wilson, 7);
+        a.generate_full();
+        b.generate_full();
+        assert_eq!(walls(&a), walls(&b));
+    }
+
+    #[test]
+    fn different_seeds_make_different_mazes() {
+        let mut a = Grid::with_seed(15, 12, GenAlgorithm::Wilson, 7);
+        let mut b = Grid::with_seed(15, 12, GenAlgorithm::Wilson, 8);
+        a.generate_full();
+        b.generate_full();
+        assert!(!a.structurally_eq(&b));
+        assert_ne!(a.fingerprint(), b.fingerprint());
+    }
+
+    #[test]
+    fn ascii_round_trip() {
+        let mut grid = Grid::new_seeded(6, 9, 3);
+        grid.generate_full();
+        let loaded = Grid::from_ascii(&grid.to_ascii().unwrap()).unwrap();
+        assert!(loaded.structurally_eq(&grid));
+        assert!(Grid::from_ascii("+--+\n|  \n+--+\n").is_err());
+        assert!(Grid::from_ascii("").is_err());
+    }
+
+    #[test]
+    fn maze_file_round_trip() {
+        let path = std::env::temp_dir().join("puzzler_round_trip.maze");
+        let path = path.to_str().unwrap();
+        let mut grid = Grid::new_seeded(7, 5, 11);
+        grid.generate_full();
+        grid.save_maze(path).unwrap();
+        assert!(Grid::load_maze(path).unwrap().structurally_eq(&grid));
+
+        let bytes = std::fs::read(path).unwrap();
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        std::fs::write(path, bad_magic).unwrap();
+        assert!(Grid::load_maze(path).is_err());
+
+        std::fs::write(path, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(Grid::load_maze(path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn walls_open_and_close() {
+        let mut walls = Walls::ALL;
+        assert!(!walls.is_open(Direction::North));
+        walls.open(Direction::North);
+        assert!(walls.is_open(Direction::North));
+        assert!(!walls.is_open(Direction::East));
+        walls.set_open(Direction::North, false);
+        assert_eq!(walls, Walls::ALL);
+
+        walls.set(Walls::LEFT, false);
+        assert!(walls.is_open(Direction::West));
+        assert!(!walls.contains(Walls::LEFT));
+        assert!(walls.contains(Walls::TOP));
+        assert_eq!(Walls::from_bits(walls.bits()), walls);
+        assert_eq!(
+            Walls::from_bits(0b0101).bits(),
+            Walls::TOP.bits() | Walls::BOTTOM.bits()
+        );
+    }
+
+    #[test]
+    fn cells_from_before_walls_still_load() {
+        let json = r#"{"row":2,"col":3,"visited":true,"filled":false,
+            "top":false,"bot":true,"left":true,"right":false}"#;
+        let cell: Cell = serde_json::from_str(json).unwrap();
+        assert_eq!((cell.layer, cell.row, cell.col), (0, 2, 3));
+        assert!(cell.walls.is_open(Direction::North));
+        assert!(cell.walls.is_open(Direction::East));
+        assert!(!cell.walls.is_open(Direction::South));
+        assert!(!cell.walls.is_open(Direction::West));
+        for walls in [Walls::TOP_RIGHT, Walls::BOTTOM_LEFT, Walls::UP, Walls::DOWN] {
+            assert!(cell.walls.contains(walls));
+        }
+    }
+
+    #[test]
+    fn tiny_grids() {
+        let empty = Grid::new_seeded(0, 0, 1);
+        assert_eq!((empty.rows, empty.cols), (1, 1));
+        assert!(empty.finished);
+
+        let mut single = Grid::new_seeded(1, 1, 1);
+        assert!(single.finished);
+        assert!(single.is_perfect());
+        single.generate_full();
+        assert_eq!(single.shortest_path(), Some(vec![0]));
+
+        let mut algorithm = GenAlgorithm::RecursiveBacktracker;
+        loop {
+            for (rows, cols) in [(1, 9), (9, 1)] {
+                let mut grid = Grid::with_seed(rows, cols, algorithm, 5);
+                grid.generate_full();
+                assert!(
+                    grid.is_perfect(),
+                    "{} on {}x{}",
+                    algorithm.name(),
+                    rows,
+                    cols
+                );
+            }
+            algorithm = algorithm.cycle();
+            if algorithm == GenAlgorithm::RecursiveBacktracker {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn aldous_broder_visits_every_cell() {
+        let mut grid = Grid::with_seed(12, 12, GenAlgorithm::AldousBroder, 9);
+        grid.generate_full();
+        assert!(grid.cells.iter().all(|cell| cell.visited));
+    }
+
+    #[test]
+    fn recursive_division_is_perfect() {
+        let mut grid = Grid::with_seed(10, 10, GenAlgorithm::RecursiveDivision, 3);
+        grid.generate_full();
+        assert!(grid.is_perfect());
+    }
+
+    #[test]
+    fn dijkstra_goes_around_a_heavy_cell() {
+        let room = "\
++---+---+---+
+|           |
++   +   +   +
+|           |
++   +   +   +
+|           |
++---+---+---+
+";
+        let mut grid = Grid::from_ascii(room).unwrap();
+        grid.weights[4] = 10;
+        let (cost, path) = grid.solve_dijkstra().unwrap();
+        assert_eq!(cost, 4);
+        assert_eq!(path.len(), 5);
+        assert!(!path.contains(&4));
+    }
+
+    #[test]
+    fn dead_end_filling_leaves_a_corridor() {
+        let mut grid = Grid::new_seeded(15, 15, 21);
+        grid.generate_full();
+        grid.solve(SolveAlgorithm::DeadEndFilling);
+        while grid.solver.is_some() {
+            grid.solve_step();
+        }
+        for index in 0..grid.cells.len() {
+            if grid.cells[index].filled || index == grid.start || index == grid.goal {
+                continue;
+            }
+            assert_eq!(grid.unfilled_neighbors(index).len(), 2);
+        }
+        assert_eq!(grid.solution, grid.shortest_path());
+    }
+
+    #[test]
+    fn full_braid_leaves_no_dead_ends() {
+        let mut grid = Grid::new_seeded(12, 12, 4);
+        grid.generate_full();
+        assert!(!grid.dead_ends().is_empty());
+        grid.braid(1.0);
+        assert!(grid.dead_ends().is_empty());
+        assert!(grid.is_connected());
+    }
+
+    #[test]
+    fn triangle_neighbors_are_mutual() {
+        for wrap in [(false, false), (true, true)] {
+            let grid = Grid::with_layers(
+                6,
+                8,
+                1,
+                Topology::Triangle,
+                wrap,
+                GenAlgorithm::RecursiveBacktracker,
+                1,
+            );
+            for index in 0..grid.cells.len() {
+                assert!(grid.neighbors(index).len() <= 3);
+                for neighbor in grid.neighbors(index) {
+                    assert!(grid
+                        .neighbors(neighbor)
+                        .into_iter()
+                        .any(|back| back == index));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn layers_are_connected_by_stairs() {
+        let mut grid = Grid::with_layers(
+            6,
+            6,
+            3,
+            Topology::Square,
+            (false, false),
+            GenAlgorithm::Kruskal,
+            2,
+        );
+        grid.generate_full();
+        assert!(grid.is_perfect());
+        for layer in 0..2 {
+            let stairs = (0..grid.cells.len())
+                .filter(|&index| grid.cells[index].layer == layer)
+                .flat_map(|index| grid.open_neighbors(index))
+                .any(|neighbor| grid.cells[neighbor].layer == layer + 1);
+            assert!(stairs, "no stairs up from layer {}", layer);
+        }
+    }
+
+    #[test]
+    fn paths_cross_the_wrapping_edge() {
+        let mut grid = Grid::with_layers(
+            8,
+            8,
+            1,
+            Topology::Square,
+            (true, false),
+            GenAlgorithm::Kruskal,
+            6,
+        );
+        grid.generate_full();
+        assert!(grid.is_perfect());
+        let seam: Vec<(usize, usize)> = (0..8)
+            .map(|row| (row * 8, row * 8 + 7))
+            .filter(|&(left, right)| grid.wall_open(left, right))
+            .collect();
+        assert!(!seam.is_empty());
+        for (left, right) in seam {
+            assert_eq!(
+                grid.path_between(left, right, Grid::moves),
+                Some(vec![left, right])
+            );
+        }
+    }
+
+    #[test]
+    fn longest_path_of_a_winding_corridor() {
+        let corridor = "\
++---+---+---+
+|           |
++---+---+   +
+|           |
++   +---+---+
+|           |
++---+---+---+
+";
+        let grid = Grid::from_ascii(corridor).unwrap();
+        let mut path = grid.longest_path();
+        if path[0] != 0 {
+            path.reverse();
+        }
+        assert_eq!(path, vec![0, 1, 2, 5, 4, 3, 6, 7, 8]);
+    }
+
+    /// Times `get_random_neighbor` against the `Vec` version it replaced at
+    /// every step of a 100x100 maze. Run with `cargo test --release --
+    /// --ignored`, since only an optimized build says anything about speed.
+    #[test]
+    #[ignore]
+    fn bench_random_neighbor() {
+        const REPEATS: usize = 50;
+        let mut grid = Grid::new_seeded(100, 100, 1);
+        let (mut stack, mut vec) = (Duration::ZERO, Duration::ZERO);
+        while !grid.finished {
+            let seed = grid.rng.get_seed();
+            let started = Instant::now();
+            for _ in 0..REPEATS {
+                std::hint::black_box(grid.get_random_neighbor());
+            }
+            stack += started.elapsed();
+            let started = Instant::now();
+            for _ in 0..REPEATS {
+                std::hint::black_box(grid.get_random_neighbor_vec());
+            }
+            vec += started.elapsed();
+
+            grid.rng.seed(seed);
+            let picked = grid.get_random_neighbor();
+            grid.rng.seed(seed);
+            assert_eq!(picked, grid.get_random_neighbor_vec());
+            grid.rng.seed(seed);
+            grid.step();
+        }
+        assert!(stack < vec, "Neighbors took {:?} and Vec {:?}", stack, vec);
+    }
+
+    #[test]
+    fn opposite_directions() {
+        let directions = [
+            Direction::North,
+            Direction::East,
+            Direction::South,
+            Direction::West,
+            Direction::NorthEast,
+            Direction::SouthWest,
+        ];
+        for direction in directions {
+            let (row, col) = direction.offset();
+            assert_eq!(direction.opposite().offset(), (-row, -col));
+            assert_eq!(direction.opposite().opposite(), direction);
+            assert_eq!(Direction::from_offset((row, col)), Some(direction));
+        }
+        assert_eq!(Direction::North.opposite(), Direction::South);
+        assert_eq!(Direction::from_offset((1, 1)), None);
+    }
+
+    #[test]
+    fn iter_cells_goes_row_by_row() {
+        let grid = Grid::new_seeded(3, 4, 1);
+        let coordinates: Vec<(usize, usize)> =
+            grid.iter_cells().map(|(row, col, _)| (row, col)).collect();
+        let expected: Vec<(usize, usize)> = (0..3)
+            .flat_map(|row| (0..4).map(move |col| (row, col)))
+            .collect();
+        assert_eq!(coordinates, expected);
+        for (row, col, cell) in grid.iter_cells() {
+            assert_eq!((cell.row, cell.col), (row, col));
+        }
+    }
+
+    #[test]
+    fn backtracker_backs_up_when_stuck() {
+        let mut grid = Grid::new_seeded(5, 5, 3);
+        let mut backtracked = 0;
+        while !grid.finished {
+            let stuck = grid
+                .neighbors(grid.current)
+                .into_iter()
+                .all(|neighbor| grid.cells[neighbor].visited);
+            let walls: Vec<Walls> = grid.cells.iter().map(|cell| cell.walls).collect();
+            let top = grid.stack.back().copied();
+            grid.step();
+            if stuck {
+                backtracked += 1;
+                if let Some(top) = top {
+                    assert_eq!(grid.current, top);
+                    assert!(grid.cells.iter().map(|cell| cell.walls).eq(walls));
+                }
+            }
+        }
+        assert!(backtracked > 0);
+        assert!(grid.is_perfect());
+    }
+
+    #[test]
+    fn parallel_regions_are_joined() {
+        for regions in [2, 4, 7] {
+            let mut grid = Grid::with_seed(40, 30, GenAlgorithm::Prim, 13);
+            grid.generate_parallel(regions);
+            assert!(grid.finished);
+            assert!(grid.is_connected(), "{} regions", regions);
+            assert!(grid.is_perfect(), "{} regions", regions);
+        }
+    }
+
+    #[test]
+    fn diagonal_moves_dont_cut_corners() {
+        let bend = "\
++---+---+
+|       |
++---+   +
+|       |
++---+---+
+";
+        let grid = Grid::from_ascii(bend).unwrap();
+        assert_eq!(grid.step_diagonally(0, vec2(1.0, 1.0)), Some(3));
+        assert_eq!(grid.step_diagonally(3, vec2(-1.0, -1.0)), Some(0));
+        assert_eq!(grid.step_diagonally(1, vec2(-1.0, 1.0)), Some(2));
+        assert_eq!(grid.step_diagonally(0, vec2(1.0, 0.0)), None);
+
+        let walled_in = "\
++---+---+
+|   |   |
++---+   +
+|       |
++---+---+
+";
+        let grid = Grid::from_ascii(walled_in).unwrap();
+        assert_eq!(grid.step_diagonally(0, vec2(1.0, 1.0)), None);
+        assert_eq!(grid.step_diagonally(3, vec2(-1.0, -1.0)), None);
+        assert_eq!(grid.step_diagonally(1, vec2(-1.0, 1.0)), Some(2));
+    }
+
+    #[test]
+    fn stretched_cells_leave_no_gaps() {
+        // 20 columns and 10 rows filling a 1600x600 window
+        let (rows, cols, cell_w, cell_h) = (10, 20, 80.0, 60.0);
+        let mut grid = Grid::blank(
+            rows,
+            cols,
+            1,
+            Topology::Square,
+            GenAlgorithm::RecursiveBacktracker,
+            0,
+        );
+        grid.set_cell_size((cell_w, cell_h));
+        let bounds = |index: usize| -> (f32, f32, f32, f32) {
+            let points: Vec<Vec2> = grid
+                .walls(index)
+                .into_iter()
+                .flat_map(|(from, to)| [from, to])
+                .collect();
+            let min = points
+                .iter()
+                .fold(Vec2::splat(f32::MAX), |min, &p| min.min(p));
+            let max = points
+                .iter()
+                .fold(Vec2::splat(f32::MIN), |max, &p| max.max(p));
+            return (min.x, min.y, max.x, max.y);
+        };
+
+        for index in 0..grid.cells.len() {
+            let (row, col) = (grid.cells[index].row, grid.cells[index].col);
+            let (left, top, right, bottom) = bounds(index);
+            assert_eq!((right - left, bottom - top), (cell_w, cell_h));
+            if col + 1 < cols {
+                assert_eq!(bounds(index + 1).0, right);
+            } else {
+                assert_eq!(right, cols as f32 * cell_w);
+            }
+            if row + 1 < rows {
+                assert_eq!(bounds(index + cols).1, bottom);
+            } else {
+                assert_eq!(bottom, rows as f32 * cell_h);
+            }
+
+            let center = grid.center(index);
+            assert_eq!(center, vec2((left + right) / 2.0, (top + bottom) / 2.0));
+            assert_eq!(grid.cell_at(center), Some(index));
+            let corner = vec2(cell_w, cell_h) * 0.49;
+            assert_eq!(grid.cell_at(center - corner), Some(index));
+            assert_eq!(grid.cell_at(center + corner), Some(index));
+        }
+    }
+
+    #[test]
+    fn square_cells_fit_a_wide_window() {
+        for (width, height) in [(1200.0, 600.0), (1210.0, 615.0), (600.0, 1200.0)] {
+            let (rows, cols) = Topology::Square.fit(width, height, WALL_WIDTH);
+            assert_eq!(rows, (height / CELL_SIZE) as usize);
+            assert_eq!(cols, (width / CELL_SIZE) as usize);
+            assert!(cols as f32 * CELL_SIZE <= width);
+            assert!(rows as f32 * CELL_SIZE <= height);
+            assert!((cols + 1) as f32 * CELL_SIZE > width);
+            assert!((rows + 1) as f32 * CELL_SIZE > height);
+        }
+        assert_eq!(Topology::Square.fit(1200.0, 600.0, WALL_WIDTH), (30, 60));
+        assert_eq!(Topology::Square.fit(5.0, 5.0, WALL_WIDTH), (1, 1));
+    }
+}
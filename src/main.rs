@@ -1,265 +1,3692 @@
+#![allow(clippy::needless_return)]
+
 use macroquad::prelude::*;
-use std::collections::VecDeque;
+use puzzler::*;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+const DEFAULT_STEPS_PER_FRAME: usize = 1;
+const SOLVER_STEPS_PER_FRAME: usize = 1;
+/// Cells per second the auto-solver walks by default.
+const AUTO_SOLVE_SPEED: f32 = 8.0;
+const BRAID_FRACTION: f32 = 0.5;
+/// How many of the straight corridors `E` turns into crossings.
+const WEAVE_FRACTION: f32 = 0.3;
+/// How much `+` and `-` change the straightness of the backtracker in bias mode.
+const STRAIGHTNESS_STEP: f32 = 0.1;
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 10.0;
+/// How quickly the view catches up with the generator in follow mode, and zooms
+/// out to the whole maze once it's done, as the share of the way per second.
+const FOLLOW_RATE: f32 = 4.0;
+const SOLUTION_WIDTH: f32 = 4.0;
+/// Lines per mesh of a `WallMesh`, each of which takes 4 vertices and 6 indices.
+const LINES_PER_MESH: usize = 800;
+/// Square cells smaller than this many pixels on screen have their walls drawn
+/// from an `Overview` instead of line by line.
+const OVERVIEW_CELL_PIXELS: f32 = 8.0;
+/// Texels across a cell of an `Overview`, one of them for the wall. Walls
+/// thicker than that get by with fewer.
+const OVERVIEW_TEXELS: usize = 3;
+/// The widest and highest an `Overview` texture gets; bigger mazes keep
+/// their lines when zoomed out.
+const MAX_OVERVIEW_SIZE: usize = 8192;
+const PLAYER_RADIUS: f32 = CELL_SIZE / 3.0;
+/// How many portals `Y` places, and the colors that tell them apart.
+const PORTAL_PAIRS: usize = 3;
+const PORTAL_COLORS: [Color; 3] = [MAGENTA, LIME, ORANGE];
+const PORTAL_WIDTH: f32 = 3.0;
+const COIN_RADIUS: f32 = CELL_SIZE / 5.0;
+/// How opaque the colors of the fill tool are.
+const FILL_ALPHA: f32 = 0.6;
+/// How opaque the oldest and the newest cells of the breadcrumb trail are.
+const TRAIL_FADE: (f32, f32) = (0.1, 0.5);
+const DEFAULT_FOG_RADIUS: usize = 3;
+/// How many steps from the start each band of color covers by default, and the
+/// colors the bands cycle through.
+const DEFAULT_BAND_SIZE: u32 = 5;
+const BAND_COLORS: [Color; 6] = [SKYBLUE, LIME, GOLD, ORANGE, PINK, VIOLET];
+/// How much of a cell the player has seen before but can't see now is hidden.
+const EXPLORED_FOG: f32 = 0.7;
+/// How many generator steps `--record` takes between two frames by default.
+const DEFAULT_RECORD_STRIDE: usize = 10;
+const FRAME_DELAY_MS: u32 = 40;
+/// How long the finished maze stays on screen before the GIF starts over.
+const LAST_FRAME_DELAY_MS: u32 = 3000;
+const PROGRESS_BAR_HEIGHT: f32 = 4.0;
+/// How long "Done" stays on screen once the generator finished, in seconds.
+const DONE_FLASH_SECONDS: f64 = 1.5;
+/// How far a stick has to be pushed, out of 1, before it counts as an arrow.
+#[cfg(not(target_arch = "wasm32"))]
+const STICK_DEADZONE: f32 = 0.5;
+/// How often to poll the gamepads a frame, each time reading at most one
+/// change of every gamepad.
+#[cfg(not(target_arch = "wasm32"))]
+const GAMEPAD_POLLS: usize = 16;
+/// Whether this is the WebAssembly build, which runs in a browser.
+const IN_BROWSER: bool = cfg!(target_arch = "wasm32");
+/// The size of the maze `--tui` draws, unless given, to fit most terminals.
+const TUI_ROWS: usize = 10;
+const TUI_COLS: usize = 20;
+
+/// Colors used to draw the maze and its overlays.
+#[derive(Debug, Clone, Copy)]
+struct Theme {
+    visited: Color,
+    current: Color,
+    walk: Color,
+    /// The cells on the path of the recursive backtracker.
+    stack: Color,
+    erased: Color,
+    hunt: Color,
+    frontier: Color,
+    searched: Color,
+    closed: Color,
+    trail: Color,
+    solution: Color,
+    longest: Color,
+    filled: Color,
+    stairs: Color,
+    wrap: Color,
+    player: Color,
+    /// The player on the WASD keys in a race.
+    second_player: Color,
+    start: Color,
+    goal: Color,
+    /// Heatmap colors of the cells closest to and farthest from the start.
+    near: Color,
+    far: Color,
+    unreachable: Color,
+    /// The cells that cost more to walk through.
+    swamp: Color,
+    coin: Color,
+    foreground: Color,
+    background: Color,
+}
+
+impl Theme {
+    const DARK: Theme = Theme {
+        visited: DARKPURPLE,
+        current: DARKBLUE,
+        walk: DARKGREEN,
+        stack: Color::new(0.0, 0.4, 0.45, 1.0),
+        erased: MAROON,
+        hunt: DARKBROWN,
+        frontier: ORANGE,
+        searched: BROWN,
+        closed: DARKGRAY,
+        trail: GOLD,
+        solution: SKYBLUE,
+        longest: GOLD,
+        filled: Color::new(0.15, 0.15, 0.15, 1.0),
+        stairs: VIOLET,
+        wrap: PINK,
+        player: YELLOW,
+        second_player: LIME,
+        start: GREEN,
+        goal: RED,
+        near: DARKBLUE,
+        far: ORANGE,
+        unreachable: MAGENTA,
+        swamp: Color::new(0.3, 0.4, 0.1, 0.6),
+        coin: GOLD,
+        foreground: WHITE,
+        background: BLACK,
+    };
+
+    const LIGHT: Theme = Theme {
+        visited: Color::new(0.87, 0.82, 0.96, 1.0),
+        current: SKYBLUE,
+        walk: LIME,
+        stack: Color::new(0.7, 0.9, 0.85, 1.0),
+        erased: PINK,
+        hunt: BEIGE,
+        frontier: ORANGE,
+        searched: Color::new(0.93, 0.84, 0.66, 1.0),
+        closed: LIGHTGRAY,
+        trail: GOLD,
+        solution: BLUE,
+        longest: ORANGE,
+        filled: Color::new(0.8, 0.8, 0.8, 1.0),
+        stairs: VIOLET,
+        wrap: MAGENTA,
+        player: PURPLE,
+        second_player: Color::new(0.0, 0.5, 0.5, 1.0),
+        start: GREEN,
+        goal: RED,
+        near: SKYBLUE,
+        far: RED,
+        unreachable: DARKGRAY,
+        swamp: Color::new(0.55, 0.65, 0.3, 0.5),
+        coin: Color::new(0.8, 0.6, 0.0, 1.0),
+        foreground: BLACK,
+        background: WHITE,
+    };
+
+    const SOLARIZED: Theme = Theme {
+        visited: color_u8!(0x07, 0x36, 0x42, 0xff),
+        current: color_u8!(0x26, 0x8b, 0xd2, 0xff),
+        walk: color_u8!(0x2a, 0xa1, 0x98, 0xff),
+        stack: color_u8!(0x6c, 0x71, 0xc4, 0xff),
+        erased: color_u8!(0xd3, 0x36, 0x82, 0xff),
+        hunt: color_u8!(0xcb, 0x4b, 0x16, 0xff),
+        frontier: color_u8!(0xb5, 0x89, 0x00, 0xff),
+        searched: color_u8!(0x6c, 0x71, 0xc4, 0xff),
+        closed: color_u8!(0x58, 0x6e, 0x75, 0xff),
+        trail: color_u8!(0xb5, 0x89, 0x00, 0xff),
+        solution: color_u8!(0x2a, 0xa1, 0x98, 0xff),
+        longest: color_u8!(0xcb, 0x4b, 0x16, 0xff),
+        filled: color_u8!(0x00, 0x1e, 0x26, 0xff),
+        stairs: color_u8!(0x6c, 0x71, 0xc4, 0xff),
+        wrap: color_u8!(0xd3, 0x36, 0x82, 0xff),
+        player: color_u8!(0xcb, 0x4b, 0x16, 0xff),
+        second_player: color_u8!(0x26, 0x8b, 0xd2, 0xff),
+        start: color_u8!(0x85, 0x99, 0x00, 0xff),
+        goal: color_u8!(0xdc, 0x32, 0x2f, 0xff),
+        near: color_u8!(0x26, 0x8b, 0xd2, 0xff),
+        far: color_u8!(0xd3, 0x36, 0x82, 0xff),
+        unreachable: color_u8!(0x58, 0x6e, 0x75, 0xff),
+        swamp: color_u8!(0x85, 0x99, 0x00, 0x80),
+        coin: color_u8!(0xb5, 0x89, 0x00, 0xff),
+        foreground: color_u8!(0x93, 0xa1, 0xa1, 0xff),
+        background: color_u8!(0x00, 0x2b, 0x36, 0xff),
+    };
+
+    /// The Okabe-Ito colors, which stay apart with deuteranopia and
+    /// protanopia, on black. Cells that cover most of the maze get darker
+    /// shades, so the walls and highlights stand out against them.
+    const COLORBLIND: Theme = Theme {
+        visited: color_u8!(0x00, 0x39, 0x59, 0xff),
+        current: color_u8!(0xf0, 0xe4, 0x42, 0xff),
+        walk: color_u8!(0x00, 0x9e, 0x73, 0xff),
+        stack: color_u8!(0x56, 0xb4, 0xe9, 0xff),
+        erased: color_u8!(0xcc, 0x79, 0xa7, 0xff),
+        hunt: color_u8!(0xe6, 0x9f, 0x00, 0xff),
+        frontier: color_u8!(0xe6, 0x9f, 0x00, 0xff),
+        searched: color_u8!(0x2b, 0x5a, 0x75, 0xff),
+        closed: color_u8!(0x40, 0x40, 0x40, 0xff),
+        trail: color_u8!(0xf0, 0xe4, 0x42, 0xff),
+        solution: color_u8!(0xe6, 0x9f, 0x00, 0xff),
+        longest: color_u8!(0xcc, 0x79, 0xa7, 0xff),
+        filled: color_u8!(0x26, 0x26, 0x26, 0xff),
+        stairs: color_u8!(0xcc, 0x79, 0xa7, 0xff),
+        wrap: color_u8!(0x56, 0xb4, 0xe9, 0xff),
+        player: color_u8!(0xf0, 0xe4, 0x42, 0xff),
+        second_player: color_u8!(0x56, 0xb4, 0xe9, 0xff),
+        start: color_u8!(0x00, 0x9e, 0x73, 0xff),
+        goal: color_u8!(0xd5, 0x5e, 0x00, 0xff),
+        near: color_u8!(0x00, 0x72, 0xb2, 0xff),
+        far: color_u8!(0xe6, 0x9f, 0x00, 0xff),
+        unreachable: color_u8!(0xcc, 0x79, 0xa7, 0xff),
+        swamp: color_u8!(0x00, 0x9e, 0x73, 0x60),
+        coin: color_u8!(0xe6, 0x9f, 0x00, 0xff),
+        foreground: WHITE,
+        background: BLACK,
+    };
+
+    const PRESETS: [Theme; 4] = [
+        Theme::DARK,
+        Theme::LIGHT,
+        Theme::SOLARIZED,
+        Theme::COLORBLIND,
+    ];
+    const NAMES: [&'static str; 4] = ["dark", "light", "solarized", "colorblind"];
 
-const CELL_SIZE: f32 = 20.0;
-const WALL_WIDTH: f32 = 2.0;
-const HIGHLIGHT_COLOR1: Color = DARKPURPLE;
-const HIGHLIGHT_COLOR2: Color = DARKBLUE;
-const FOREGROUND_COLOR: Color = WHITE;
-const BACKGROUND_COLOR: Color = BLACK;
+    /// The color called `name`, like the field that holds it.
+    fn color_mut(&mut self, name: &str) -> Option<&mut Color> {
+        return match name {
+            "visited" => Some(&mut self.visited),
+            "current" => Some(&mut self.current),
+            "walk" => Some(&mut self.walk),
+            "stack" => Some(&mut self.stack),
+            "erased" => Some(&mut self.erased),
+            "hunt" => Some(&mut self.hunt),
+            "frontier" => Some(&mut self.frontier),
+            "searched" => Some(&mut self.searched),
+            "closed" => Some(&mut self.closed),
+            "trail" => Some(&mut self.trail),
+            "solution" => Some(&mut self.solution),
+            "longest" => Some(&mut self.longest),
+            "filled" => Some(&mut self.filled),
+            "stairs" => Some(&mut self.stairs),
+            "wrap" => Some(&mut self.wrap),
+            "player" => Some(&mut self.player),
+            "second_player" => Some(&mut self.second_player),
+            "start" => Some(&mut self.start),
+            "goal" => Some(&mut self.goal),
+            "near" => Some(&mut self.near),
+            "far" => Some(&mut self.far),
+            "unreachable" => Some(&mut self.unreachable),
+            "swamp" => Some(&mut self.swamp),
+            "coin" => Some(&mut self.coin),
+            "foreground" => Some(&mut self.foreground),
+            "background" => Some(&mut self.background),
+            _ => None,
+        };
+    }
+}
 
-fn index(row: i32, col: i32, rows: i32, cols: i32) -> Option<usize> {
-    if row < 0 || col < 0 || row > rows - 1 || col > cols - 1 {
+/// The color written as `#rrggbb` or `#rrggbbaa`.
+fn parse_color(text: &str) -> Option<Color> {
+    let digits = text.strip_prefix('#')?;
+    if !matches!(digits.len(), 6 | 8) || !digits.is_ascii() {
         return None;
     }
-    return Some((row * rows + col) as usize);
+    let channel = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).ok();
+    let alpha = if digits.len() == 8 { channel(6)? } else { 0xff };
+    return Some(color_u8!(channel(0)?, channel(2)?, channel(4)?, alpha));
+}
+
+/// Fills the part of the ring between `inner` and `outer` that lies between
+/// the angles `from` and `to`.
+fn draw_sector(origin: Vec2, inner: f32, outer: f32, from: f32, to: f32, color: Color) {
+    let segments = arc_segments(outer, from, to);
+    let step = (to - from) / segments as f32;
+    for i in 0..segments {
+        let (a, b) = (from + step * i as f32, from + step * (i + 1) as f32);
+        let (inner_a, inner_b) = (polar(origin, inner, a), polar(origin, inner, b));
+        let (outer_a, outer_b) = (polar(origin, outer, a), polar(origin, outer, b));
+        draw_triangle(inner_a, outer_a, outer_b, color);
+        draw_triangle(inner_a, outer_b, inner_b, color);
+    }
+}
+
+/// Lines drawn with a few `draw_mesh` calls instead of one `draw_line` each.
+/// They are spread over several meshes, because macroquad cuts off the ones
+/// with more than 5000 indices.
+struct WallMesh {
+    meshes: Vec<Mesh>,
+    /// Stretched along every line, from one end to the other.
+    texture: Option<Texture2D>,
+}
+
+impl WallMesh {
+    fn new(texture: Option<Texture2D>) -> Self {
+        return Self {
+            meshes: Vec::new(),
+            texture,
+        };
+    }
+
+    /// Adds the same quad `draw_line` would draw.
+    fn push_line(&mut self, from: Vec2, to: Vec2, thickness: f32, color: Color) {
+        let normal = vec2(from.y - to.y, to.x - from.x);
+        if normal.length() < f32::EPSILON {
+            return;
+        }
+        let offset = normal.normalize() * thickness / 2.0;
+
+        let full = |mesh: &Mesh| mesh.vertices.len() >= LINES_PER_MESH * 4;
+        if self.meshes.last().is_none_or(full) {
+            self.meshes.push(Mesh {
+                vertices: Vec::new(),
+                indices: Vec::new(),
+                texture: self.texture,
+            });
+        }
+        let mesh = self
+            .meshes
+            .last_mut()
+            .expect("a mesh with room was just added");
+        let first = mesh.vertices.len() as u16;
+        let corners = [from + offset, from - offset, to + offset, to - offset];
+        let uvs = [
+            vec2(0.0, 0.0),
+            vec2(0.0, 1.0),
+            vec2(1.0, 0.0),
+            vec2(1.0, 1.0),
+        ];
+        for (corner, uv) in corners.into_iter().zip(uvs) {
+            mesh.vertices.push(macroquad::models::Vertex {
+                position: corner.extend(0.0),
+                uv,
+                color,
+            });
+        }
+        mesh.indices.extend([0, 1, 2, 2, 1, 3].map(|i| first + i));
+    }
+
+    fn draw(&self) {
+        for mesh in self.meshes.iter() {
+            draw_mesh(mesh);
+        }
+    }
+}
+
+/// The walls of a square maze as a texture with a few texels per cell, drawn
+/// instead of the `WallMesh` when the cells get so small that lines thinner
+/// than a pixel would flicker and run together.
+struct Overview {
+    texture: Texture2D,
+    /// World position of the top left corner and world size of the texture.
+    corner: Vec2,
+    size: Vec2,
+}
+
+impl Overview {
+    fn draw(&self) {
+        let params = DrawTextureParams {
+            dest_size: Some(self.size),
+            ..Default::default()
+        };
+        draw_texture_ex(self.texture, self.corner.x, self.corner.y, WHITE, params);
+    }
+}
+
+impl Drop for Overview {
+    fn drop(&mut self) {
+        // macroquad doesn't free textures by itself
+        self.texture.delete();
+    }
 }
 
+/// Whether `grid` is better drawn from an `Overview` at the zoom of `view`.
+fn shows_overview(grid: &Grid, view: &View) -> bool {
+    let (cell_w, cell_h) = grid.cell_size();
+    let largest = grid.rows.max(grid.cols) * OVERVIEW_TEXELS + 1;
+    return grid.topology == Topology::Square
+        && cell_w.min(cell_h) * view.zoom < OVERVIEW_CELL_PIXELS
+        && largest <= MAX_OVERVIEW_SIZE;
+}
+
+/// How the walls and floors are drawn in the window, besides their colors:
+/// with the textures given with `--floor` and `--wall` instead of flat colors,
+/// if any, and how thick the walls are. The floor is only drawn on square cells.
 #[derive(Debug, Clone, Copy)]
-struct Cell {
-    row: usize,
-    col: usize,
-    visited: bool,
-    top: bool,
-    bot: bool,
-    left: bool,
-    right: bool,
-}
-
-impl Default for Cell {
-    fn default() -> Self {
+struct Skin {
+    floor: Option<Texture2D>,
+    wall: Option<Texture2D>,
+    wall_width: f32,
+}
+
+impl Skin {
+    async fn load(args: &Args) -> Self {
         return Self {
-            row: 0,
-            col: 0,
-            visited: false,
-            top: true,
-            bot: true,
-            left: true,
-            right: true,
+            floor: load_skin_texture(args.floor.as_deref()).await,
+            wall: load_skin_texture(args.wall.as_deref()).await,
+            wall_width: args.config.wall_width(),
         };
     }
 }
 
-impl Cell {
-    fn highlight(&self, color: Color) {
-        let x = self.col as f32 * CELL_SIZE;
-        let y = self.row as f32 * CELL_SIZE;
+/// The texture at `path`, if there is one; the flat colors stay in its place
+/// if it can't be loaded.
+async fn load_skin_texture(path: Option<&str>) -> Option<Texture2D> {
+    let path = path?;
+    return match load_texture(path).await {
+        Ok(texture) => Some(texture),
+        Err(err) => {
+            eprintln!("error: could not load {}: {}", path, err);
+            None
+        }
+    };
+}
+
+/// Drawing a cell in the window, in the shape of each topology.
+trait DrawCell {
+    /// Colors the cell, `cell_size` wide and high like `Grid::cell_size`.
+    fn highlight(&self, cell_size: (f32, f32), color: Color);
+
+    /// Like `highlight`, but leaves the walls of the cell visible, which are
+    /// `wall_width` thick.
+    fn mark(&self, cell_size: (f32, f32), color: Color, wall_width: f32);
+
+    /// Colors the cell, or covers it with `floor` if it was visited.
+    fn fill(&self, cell_size: (f32, f32), theme: &Theme, floor: Option<Texture2D>);
+
+    fn highlight_hex(&self, color: Color);
+
+    fn mark_hex(&self, color: Color, wall_width: f32);
+
+    fn fill_hex(&self, theme: &Theme);
+
+    fn highlight_triangle(&self, color: Color);
+
+    fn mark_triangle(&self, color: Color, wall_width: f32);
+
+    fn fill_triangle(&self, theme: &Theme);
+
+    fn highlight_polar(&self, origin: Vec2, size: usize, color: Color);
+
+    fn mark_polar(&self, origin: Vec2, size: usize, color: Color, wall_width: f32);
+
+    fn fill_polar(&self, theme: &Theme, origin: Vec2, size: usize);
+}
+
+impl DrawCell for Cell {
+    fn highlight(&self, (cell_w, cell_h): (f32, f32), color: Color) {
+        let x = self.col as f32 * cell_w;
+        let y = self.row as f32 * cell_h;
 
-        draw_rectangle(x, y, CELL_SIZE, CELL_SIZE, color);
+        draw_rectangle(x, y, cell_w, cell_h, color);
     }
 
-    fn draw(&self) {
-        let x = self.col as f32 * CELL_SIZE;
-        let y = self.row as f32 * CELL_SIZE;
+    fn mark(&self, (cell_w, cell_h): (f32, f32), color: Color, wall_width: f32) {
+        let x = self.col as f32 * cell_w + wall_width;
+        let y = self.row as f32 * cell_h + wall_width;
+        let (w, h) = (cell_w - 2.0 * wall_width, cell_h - 2.0 * wall_width);
+
+        draw_rectangle(x, y, w, h, color);
+    }
 
-        if self.visited {
-            self.highlight(HIGHLIGHT_COLOR1);
+    fn fill(&self, cell_size: (f32, f32), theme: &Theme, floor: Option<Texture2D>) {
+        if self.blocked {
+            self.highlight(cell_size, theme.foreground);
+        } else if self.filled {
+            self.highlight(cell_size, theme.filled);
+        } else if let (true, Some(floor)) = (self.visited, floor) {
+            let (cell_w, cell_h) = cell_size;
+            let (x, y) = (self.col as f32 * cell_w, self.row as f32 * cell_h);
+            let params = DrawTextureParams {
+                dest_size: Some(vec2(cell_w, cell_h)),
+                ..Default::default()
+            };
+            draw_texture_ex(floor, x, y, WHITE, params);
+        } else if self.visited {
+            self.highlight(cell_size, theme.visited);
         }
+    }
+
+    fn highlight_hex(&self, color: Color) {
+        let center = self.hex_center();
+        draw_poly(center.x, center.y, 6, HEX_RADIUS, 30.0, color);
+    }
+
+    fn mark_hex(&self, color: Color, wall_width: f32) {
+        let center = self.hex_center();
+        draw_poly(center.x, center.y, 6, HEX_RADIUS - wall_width, 30.0, color);
+    }
 
-        // up
-        if self.top {
-            draw_line(x, y, x + CELL_SIZE, y, WALL_WIDTH, FOREGROUND_COLOR);
+    fn fill_hex(&self, theme: &Theme) {
+        if self.blocked {
+            self.highlight_hex(theme.foreground);
+        } else if self.filled {
+            self.highlight_hex(theme.filled);
+        } else if self.visited {
+            self.highlight_hex(theme.visited);
         }
+    }
 
-        // down
-        if self.bot {
-            draw_line(
-                x,
-                y + CELL_SIZE,
-                x + CELL_SIZE,
-                y + CELL_SIZE,
-                WALL_WIDTH,
-                FOREGROUND_COLOR,
-            );
+    fn highlight_triangle(&self, color: Color) {
+        let [a, b, c] = self.triangle_corners();
+        draw_triangle(a, b, c, color);
+    }
+
+    fn mark_triangle(&self, color: Color, wall_width: f32) {
+        // the distance from the center to the sides is a third of the height
+        let center = self.triangle_center();
+        let scale = 1.0 - 3.0 * wall_width / TRIANGLE_HEIGHT;
+        let [a, b, c] = self
+            .triangle_corners()
+            .map(|corner| center + (corner - center) * scale);
+        draw_triangle(a, b, c, color);
+    }
+
+    fn fill_triangle(&self, theme: &Theme) {
+        if self.blocked {
+            self.highlight_triangle(theme.foreground);
+        } else if self.filled {
+            self.highlight_triangle(theme.filled);
+        } else if self.visited {
+            self.highlight_triangle(theme.visited);
         }
+    }
 
-        // left
-        if self.left {
-            draw_line(x, y, x, y + CELL_SIZE, WALL_WIDTH, FOREGROUND_COLOR);
+    fn highlight_polar(&self, origin: Vec2, size: usize, color: Color) {
+        let (inner, outer, from, to) = self.polar_bounds(size);
+        draw_sector(origin, inner, outer, from, to, color);
+    }
+
+    fn mark_polar(&self, origin: Vec2, size: usize, color: Color, wall_width: f32) {
+        let (inner, outer, from, to) = self.polar_bounds(size);
+        if self.row == 0 {
+            draw_sector(origin, 0.0, outer - wall_width, from, to, color);
+            return;
         }
+        let inset = wall_width / inner;
+        draw_sector(
+            origin,
+            inner + wall_width,
+            outer - wall_width,
+            from + inset,
+            to - inset,
+            color,
+        );
+    }
 
-        // right
-        if self.right {
-            draw_line(
-                x + CELL_SIZE,
-                y,
-                x + CELL_SIZE,
-                y + CELL_SIZE,
-                WALL_WIDTH,
-                FOREGROUND_COLOR,
-            );
+    fn fill_polar(&self, theme: &Theme, origin: Vec2, size: usize) {
+        if self.blocked {
+            self.highlight_polar(origin, size, theme.foreground);
+        } else if self.filled {
+            self.highlight_polar(origin, size, theme.filled);
+        } else if self.visited {
+            self.highlight_polar(origin, size, theme.visited);
         }
     }
 }
 
-// TODO: make `current` a mutable reference of a cell
-struct Grid {
-    rows: usize,
-    cols: usize,
-    cells: Vec<Cell>,
-    stack: VecDeque<usize>,
-    current: usize,
-    next: Option<usize>,
-}
-
-impl Grid {
-    fn new(rows: usize, cols: usize) -> Self {
-        let mut cells: Vec<Cell> = Vec::new();
-
-        for row in 0..rows {
-            for col in 0..cols {
-                cells.push(Cell {
-                    row,
-                    col,
-                    ..Default::default()
-                });
-            }
-        }
-
-        let mut grid = Self {
-            rows,
-            cols,
-            cells,
-            stack: VecDeque::new(),
-            current: fastrand::usize(..rows * cols),
-            next: None,
-        };
-        grid.update_current();
-        return grid;
-    }
-
-    fn get_random_neighbor(&self) -> Option<usize> {
-        let mut neighbors: Vec<usize> = Vec::new();
-        let cell = &self.cells[self.current];
-
-        let neighbor_index = vec![
-            index(
-                cell.row as i32 - 1,
-                cell.col as i32,
-                self.rows as i32,
-                self.cols as i32,
-            ), // Left
-            index(
-                cell.row as i32 + 1,
-                cell.col as i32,
-                self.rows as i32,
-                self.cols as i32,
-            ), // Right
-            index(
-                cell.row as i32,
-                cell.col as i32 - 1,
-                self.rows as i32,
-                self.cols as i32,
-            ), // Top
-            index(
-                cell.row as i32,
-                cell.col as i32 + 1,
-                self.rows as i32,
-                self.cols as i32,
-            ), // Bottom
-        ];
-        for maybe_index in neighbor_index {
-            if let Some(index) = maybe_index {
-                if let Some(neighbor) = self.cells.get(index) {
-                    if !neighbor.visited {
-                        neighbors.push(index);
+/// Drawing the maze in the window and into image files.
+trait DrawGrid {
+    /// Colors a visited, filled or blocked cell; its walls are drawn by the
+    /// wall mesh.
+    fn fill_cell(&self, index: usize, theme: &Theme, skin: &Skin);
+
+    /// The walls of `shown_layer` in `color`, or with the wall texture of
+    /// `skin`, to be drawn all at once.
+    fn build_wall_mesh(&self, color: Color, skin: &Skin) -> WallMesh;
+
+    /// The walls of `shown_layer` of a square grid in `color`, about
+    /// `wall_width` thick, for when its cells are too small on screen for the
+    /// wall mesh. The passages under crossings and the wall texture are left
+    /// out.
+    fn build_overview(&self, color: Color, wall_width: f32) -> Overview;
+
+    fn highlight(&self, index: usize, color: Color);
+
+    /// Like `highlight`, but leaves the walls of the cell visible, which are
+    /// `wall_width` thick.
+    fn mark(&self, index: usize, color: Color, wall_width: f32);
+
+    /// Draws a line through the centers of the cells of `path` that are on
+    /// `shown_layer`.
+    fn draw_path(&self, path: &[usize], color: Color);
+
+    /// Highlights the start, the goal and what the generator is working on,
+    /// including its path if `show_stack` is set.
+    fn draw_generation(&self, theme: &Theme, show_stack: bool);
+
+    /// The maze as an image, using the same geometry as the window but
+    /// independent of its size. With `progress` the visited cells and the one
+    /// the generator is on are colored as well. The walls are `wall_width`
+    /// thick.
+    fn render_image(&self, progress: bool, wall_width: f32) -> image::RgbaImage;
+
+    /// Writes the finished look of `render_image` to a PNG image.
+    fn export_png(&self, path: &str, wall_width: f32) -> image::ImageResult<()>;
+
+    /// Writes the maze as an SVG made of one `<line>` per wall segment, on top
+    /// of a rectangle covering the whole maze and one for each blocked cell,
+    /// with lines `wall_width` thick.
+    fn export_svg(&self, path: &str, wall_width: f32) -> std::io::Result<()>;
+}
+
+impl DrawGrid for Grid {
+    fn fill_cell(&self, index: usize, theme: &Theme, skin: &Skin) {
+        if self.cells[index].layer != self.shown_layer {
+            return;
+        }
+        match self.topology {
+            Topology::Square => self.cells[index].fill(self.cell_size(), theme, skin.floor),
+            Topology::Hex => self.cells[index].fill_hex(theme),
+            Topology::Triangle => self.cells[index].fill_triangle(theme),
+            Topology::Polar => {
+                let size = self.row_len(self.cells[index].row);
+                self.cells[index].fill_polar(theme, self.polar_origin(), size);
+            }
+        }
+    }
+
+    fn build_wall_mesh(&self, color: Color, skin: &Skin) -> WallMesh {
+        let mut mesh = WallMesh::new(skin.wall);
+        // the texture keeps its own colors
+        let color = if skin.wall.is_some() { WHITE } else { color };
+        for index in 0..self.cells.len() {
+            if self.cells[index].layer != self.shown_layer {
+                continue;
+            }
+            for (from, to) in self.walls(index) {
+                mesh.push_line(from, to, skin.wall_width, color);
+            }
+        }
+        return mesh;
+    }
+
+    fn build_overview(&self, color: Color, wall_width: f32) -> Overview {
+        let (cell_w, cell_h) = self.cell_size();
+        let texels = (cell_w.min(cell_h) / wall_width).round() as usize;
+        let texels = texels.clamp(2, OVERVIEW_TEXELS);
+        let (rows, cols) = (self.rows, self.cols);
+        let first = self.shown_layer * self.layer_len();
+        let walls = |row: usize, col: usize| self.cells[first + row * cols + col].walls;
+        // the wall above or left of a cell, or below or right of the last one
+        let horizontal = |row: usize, col: usize| -> bool {
+            if row < rows {
+                return walls(row, col).contains(Walls::TOP);
+            }
+            return walls(row - 1, col).contains(Walls::BOTTOM);
+        };
+        let vertical = |row: usize, col: usize| -> bool {
+            if col < cols {
+                return walls(row, col).contains(Walls::LEFT);
+            }
+            return walls(row, col - 1).contains(Walls::RIGHT);
+        };
+
+        let (width, height) = (cols * texels + 1, rows * texels + 1);
+        let wall: [u8; 4] = color.into();
+        // the same color, so that blending with the wall doesn't darken it
+        let clear = [wall[0], wall[1], wall[2], 0];
+        let mut bytes: Vec<u8> = Vec::with_capacity(width * height * 4);
+        for y in 0..height {
+            let (row, on_row) = (y / texels, y % texels == 0);
+            for x in 0..width {
+                let (col, on_col) = (x / texels, x % texels == 0);
+                let closed = match (on_row, on_col) {
+                    (true, true) => {
+                        row > 0 && vertical(row - 1, col)
+                            || row < rows && vertical(row, col)
+                            || col > 0 && horizontal(row, col - 1)
+                            || col < cols && horizontal(row, col)
                     }
-                }
+                    (true, false) => horizontal(row, col),
+                    (false, true) => vertical(row, col),
+                    (false, false) => false,
+                };
+                bytes.extend(if closed { wall } else { clear });
             }
         }
+        let texture = Texture2D::from_rgba8(width as u16, height as u16, &bytes);
+        texture.set_filter(FilterMode::Linear);
+
+        // centered on the lines the walls would be drawn along
+        let texel = vec2(cell_w, cell_h) / texels as f32;
+        return Overview {
+            texture,
+            corner: -texel / 2.0,
+            size: vec2(width as f32, height as f32) * texel,
+        };
+    }
 
-        if neighbors.is_empty() {
-            return None;
+    fn highlight(&self, index: usize, color: Color) {
+        if self.cells[index].layer != self.shown_layer {
+            return;
+        }
+        match self.topology {
+            Topology::Square => self.cells[index].highlight(self.cell_size(), color),
+            Topology::Hex => self.cells[index].highlight_hex(color),
+            Topology::Triangle => self.cells[index].highlight_triangle(color),
+            Topology::Polar => {
+                let size = self.row_len(self.cells[index].row);
+                self.cells[index].highlight_polar(self.polar_origin(), size, color);
+            }
         }
+    }
 
-        return Some(neighbors[fastrand::usize(..neighbors.len())]);
+    fn mark(&self, index: usize, color: Color, wall_width: f32) {
+        if self.cells[index].layer != self.shown_layer {
+            return;
+        }
+        match self.topology {
+            Topology::Square => self.cells[index].mark(self.cell_size(), color, wall_width),
+            Topology::Hex => self.cells[index].mark_hex(color, wall_width),
+            Topology::Triangle => self.cells[index].mark_triangle(color, wall_width),
+            Topology::Polar => {
+                let size = self.row_len(self.cells[index].row);
+                self.cells[index].mark_polar(self.polar_origin(), size, color, wall_width);
+            }
+        }
     }
 
-    // TODO: use if let
-    fn remove_wall(&mut self) {
-        let x = self.cells[self.current].col as i32 - self.cells[self.next.unwrap()].col as i32;
+    fn draw_path(&self, path: &[usize], color: Color) {
+        for pair in path.windows(2) {
+            let layers = (self.cells[pair[0]].layer, self.cells[pair[1]].layer);
+            if layers != (self.shown_layer, self.shown_layer) {
+                continue;
+            }
+            // a step onto a portal ends up at its other end
+            let to = if self.is_open(pair[0], pair[1]) {
+                pair[1]
+            } else {
+                self.portal_partner(pair[1]).unwrap_or(pair[1])
+            };
+            let (from, to) = (self.center(pair[0]), self.center(to));
+            draw_line(from.x, from.y, to.x, to.y, SOLUTION_WIDTH, color);
+        }
+    }
 
-        match x {
-            1 => {
-                self.cells[self.current].left = false;
-                self.cells[self.next.unwrap()].right = false;
+    fn draw_generation(&self, theme: &Theme, show_stack: bool) {
+        if let Some(row) = self.hunt_row {
+            for index in self.row_cells(self.shown_layer, row) {
+                self.highlight(index, theme.hunt);
+            }
+        }
+        if let Some(row) = self.active_row {
+            for index in self.row_cells(self.shown_layer, row) {
+                self.highlight(index, theme.current);
             }
-            -1 => {
-                self.cells[self.current].right = false;
-                self.cells[self.next.unwrap()].left = false;
+        }
+        for &index in self.walk.iter().chain(self.run.iter()) {
+            self.highlight(index, theme.walk);
+        }
+        for &index in self.erased.iter() {
+            self.highlight(index, theme.erased);
+        }
+        if show_stack {
+            for &index in self.stack.iter() {
+                self.highlight(index, theme.stack);
             }
-            _ => {}
         }
+        self.highlight(self.start, theme.start);
+        self.highlight(self.goal, theme.goal);
+        self.highlight(self.current, theme.current);
+        if let Some(next) = self.next {
+            self.highlight(next, theme.current);
+        }
+    }
+
+    fn render_image(&self, progress: bool, wall_width: f32) -> image::RgbaImage {
+        let theme = Theme::DARK;
+        // shift everything by half a wall so the outer walls are fully visible
+        let offset = wall_width / 2.0;
+        let width = (self.cols as f32 * CELL_SIZE + wall_width).ceil() as u32;
+        let height = (self.rows as f32 * CELL_SIZE + wall_width).ceil() as u32;
+        let mut image = image::RgbaImage::from_pixel(width, height, rgba(theme.background));
 
-        let y = self.cells[self.current].row as i32 - self.cells[self.next.unwrap()].row as i32;
+        let mut fill = |x: f32, y: f32, w: f32, h: f32, color: Color| {
+            let (x0, y0) = ((x + offset).max(0.0) as u32, (y + offset).max(0.0) as u32);
+            let x1 = ((x + offset + w).ceil() as u32).min(width);
+            let y1 = ((y + offset + h).ceil() as u32).min(height);
+            for py in y0..y1 {
+                for px in x0..x1 {
+                    image.put_pixel(px, py, rgba(color));
+                }
+            }
+        };
 
-        match y {
-            1 => {
-                self.cells[self.current].top = false;
-                self.cells[self.next.unwrap()].bot = false;
+        // the swamp tint as if drawn over the background
+        let swamp = lerp_color(
+            theme.background,
+            Color {
+                a: 1.0,
+                ..theme.swamp
+            },
+            theme.swamp.a,
+        );
+        for (index, (row, col, cell)) in self.iter_cells().enumerate() {
+            let (x, y) = (col as f32 * CELL_SIZE, row as f32 * CELL_SIZE);
+            if cell.blocked {
+                fill(x, y, CELL_SIZE, CELL_SIZE, theme.foreground);
+            } else if progress && cell.visited {
+                fill(x, y, CELL_SIZE, CELL_SIZE, theme.visited);
+            } else if self.weight(index) > 1 {
+                fill(x, y, CELL_SIZE, CELL_SIZE, swamp);
             }
-            -1 => {
-                self.cells[self.current].bot = false;
-                self.cells[self.next.unwrap()].top = false;
+        }
+        let mut marked = vec![(self.start, theme.start), (self.goal, theme.goal)];
+        if progress && !self.finished {
+            marked.push((self.current, theme.current));
+        }
+        for (index, color) in marked {
+            let cell = &self.cells[index];
+            let (x, y) = (cell.col as f32 * CELL_SIZE, cell.row as f32 * CELL_SIZE);
+            fill(x, y, CELL_SIZE, CELL_SIZE, color);
+        }
+
+        let half = wall_width / 2.0;
+        for cell in self.cells.iter() {
+            // images always have square cells, whatever the window does
+            for (from, to) in cell.walls((CELL_SIZE, CELL_SIZE)) {
+                let (min, max) = (from.min(to), from.max(to));
+                let (w, h) = (max.x - min.x + wall_width, max.y - min.y + wall_width);
+                fill(min.x - half, min.y - half, w, h, theme.foreground);
             }
-            _ => {}
         }
+
+        return image;
     }
 
-    fn update_current(&mut self) {
-        self.next = self.get_random_neighbor();
+    fn export_png(&self, path: &str, wall_width: f32) -> image::ImageResult<()> {
+        return self.render_image(false, wall_width).save(path);
+    }
 
-        self.cells[self.current].visited = true; // mark current cell as visited
-        if let Some(next_index) = self.next {
-            self.stack.push_back(self.current);
-            self.remove_wall();
-            self.current = next_index;
-        } else {
-            if let Some(popped) = self.stack.pop_back() {
-                self.current = popped;
+    fn export_svg(&self, path: &str, wall_width: f32) -> std::io::Result<()> {
+        let theme = Theme::DARK;
+        let width = self.cols as f32 * CELL_SIZE;
+        let height = self.rows as f32 * CELL_SIZE;
+        let offset = wall_width / 2.0;
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"{} {} {} {}\">\n",
+            width + wall_width,
+            height + wall_width,
+            -offset,
+            -offset,
+            width + wall_width,
+            height + wall_width,
+        );
+        svg += &format!(
+            "<rect x=\"0\" y=\"0\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+            width,
+            height,
+            hex(theme.background)
+        );
+        for cell in self.cells.iter().filter(|cell| cell.blocked) {
+            svg += &format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+                cell.col as f32 * CELL_SIZE,
+                cell.row as f32 * CELL_SIZE,
+                CELL_SIZE,
+                CELL_SIZE,
+                hex(theme.foreground)
+            );
+        }
+        svg += &format!(
+            "<g stroke=\"{}\" stroke-width=\"{}\" stroke-linecap=\"square\">\n",
+            hex(theme.foreground),
+            wall_width
+        );
+
+        let mut line = |x1: f32, y1: f32, x2: f32, y2: f32| {
+            svg += &format!(
+                "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"/>\n",
+                x1, y1, x2, y2
+            );
+        };
+        for (row, col, cell) in self.iter_cells() {
+            let (x, y) = (col as f32 * CELL_SIZE, row as f32 * CELL_SIZE);
+
+            // shared walls are stored on both cells; only the top and left
+            // ones are written, except along the bottom and right border
+            if cell.walls.contains(Walls::TOP) {
+                line(x, y, x + CELL_SIZE, y);
+            }
+            if cell.walls.contains(Walls::LEFT) {
+                line(x, y, x, y + CELL_SIZE);
+            }
+            if cell.walls.contains(Walls::BOTTOM) && row + 1 == self.rows {
+                line(x, y + CELL_SIZE, x + CELL_SIZE, y + CELL_SIZE);
+            }
+            if cell.walls.contains(Walls::RIGHT) && col + 1 == self.cols {
+                line(x + CELL_SIZE, y, x + CELL_SIZE, y + CELL_SIZE);
+            }
+            for (from, to) in cell.crossing_walls((CELL_SIZE, CELL_SIZE)) {
+                line(from.x, from.y, to.x, to.y);
             }
         }
+
+        svg += "</g>\n</svg>\n";
+        return std::fs::write(path, svg);
     }
 }
 
-fn window_conf() -> Conf {
-    return Conf {
-        window_resizable: false,
-        window_width: 800,
-        window_height: 800,
-        window_title: "Puzzler".to_string(),
-        ..Default::default()
-    };
+/// Writes what is in the window right now, overlays and all, to a PNG named
+/// after the current time, and returns that name.
+fn save_screenshot() -> Result<String, String> {
+    let millis = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|err| err.to_string())?
+        .as_millis();
+    let path = format!("screenshot-{}.png", millis);
+
+    let screen = get_screen_data();
+    let (width, height) = (screen.width as u32, screen.height as u32);
+    let image = image::RgbaImage::from_raw(width, height, screen.bytes)
+        .ok_or("the screen data doesn't match the size of the window")?;
+    // the rows of the framebuffer start at the bottom
+    image::imageops::flip_vertical(&image)
+        .save(&path)
+        .map_err(|err| err.to_string())?;
+    return Ok(path);
 }
 
-#[macroquad::main(window_conf)]
-async fn main() {
-    let rows = (screen_width() / CELL_SIZE).floor() as usize;
-    let cols = (screen_height() / CELL_SIZE).floor() as usize;
+fn rgba(color: Color) -> image::Rgba<u8> {
+    return image::Rgba(color.into());
+}
 
-    let mut paused = true;
-    let mut grid = Grid::new(rows, cols);
+/// The color `t` of the way from `from` to `to`.
+fn lerp_color(from: Color, to: Color, t: f32) -> Color {
+    return Color::new(
+        from.r + (to.r - from.r) * t,
+        from.g + (to.g - from.g) * t,
+        from.b + (to.b - from.b) * t,
+        from.a + (to.a - from.a) * t,
+    );
+}
 
-    loop {
-        if is_key_down(KeyCode::Q) || is_key_down(KeyCode::Escape) {
-            break;
+/// `#rrggbb` notation of `color`, ignoring its alpha.
+fn hex(color: Color) -> String {
+    let [r, g, b, _]: [u8; 4] = color.into();
+    return format!("#{:02x}{:02x}{:02x}", r, g, b);
+}
+
+/// Something a key does, named in the `[keys]` table of the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Action {
+    Quit,
+    Help,
+    Pause,
+    Step,
+    Undo,
+    Finish,
+    Faster,
+    Slower,
+    BiasMode,
+    Reset,
+    NextAlgorithm,
+    NextTopology,
+    NextPolicy,
+    Compare,
+    Braid,
+    Weave,
+    SolveBfs,
+    SolveAStar,
+    SolveDijkstra,
+    SolveWallFollower,
+    SolveDeadEndFilling,
+    ToggleSolution,
+    Play,
+    MoveUp,
+    MoveRight,
+    MoveDown,
+    MoveLeft,
+    LayerUp,
+    LayerDown,
+    ClearTrail,
+    Race,
+    AutoSolve,
+    SecondUp,
+    SecondRight,
+    SecondDown,
+    SecondLeft,
+    SecondLayerUp,
+    SecondLayerDown,
+    Portals,
+    Edit,
+    Fill,
+    ClearFills,
+    Fog,
+    FogWider,
+    FogNarrower,
+    Heatmap,
+    Bands,
+    BandsWider,
+    BandsNarrower,
+    LongestPath,
+    ShowStack,
+    Stats,
+    NextTheme,
+    ResetView,
+    Follow,
+    ExportPng,
+    ExportSvg,
+    ExportDot,
+    ExportRust,
+    PrintAscii,
+    Save,
+    Load,
+    SaveState,
+    LoadState,
+    Screenshot,
+}
+
+impl Action {
+    const ALL: [Action; 65] = [
+        Action::Quit,
+        Action::Help,
+        Action::Pause,
+        Action::Step,
+        Action::Undo,
+        Action::Finish,
+        Action::Faster,
+        Action::Slower,
+        Action::BiasMode,
+        Action::Reset,
+        Action::NextAlgorithm,
+        Action::NextTopology,
+        Action::NextPolicy,
+        Action::Compare,
+        Action::Braid,
+        Action::Weave,
+        Action::SolveBfs,
+        Action::SolveAStar,
+        Action::SolveDijkstra,
+        Action::SolveWallFollower,
+        Action::SolveDeadEndFilling,
+        Action::ToggleSolution,
+        Action::Play,
+        Action::MoveUp,
+        Action::MoveRight,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::LayerUp,
+        Action::LayerDown,
+        Action::ClearTrail,
+        Action::Race,
+        Action::AutoSolve,
+        Action::SecondUp,
+        Action::SecondRight,
+        Action::SecondDown,
+        Action::SecondLeft,
+        Action::SecondLayerUp,
+        Action::SecondLayerDown,
+        Action::Portals,
+        Action::Edit,
+        Action::Fill,
+        Action::ClearFills,
+        Action::Fog,
+        Action::FogWider,
+        Action::FogNarrower,
+        Action::Heatmap,
+        Action::Bands,
+        Action::BandsWider,
+        Action::BandsNarrower,
+        Action::LongestPath,
+        Action::ShowStack,
+        Action::Stats,
+        Action::NextTheme,
+        Action::ResetView,
+        Action::Follow,
+        Action::ExportPng,
+        Action::ExportSvg,
+        Action::ExportDot,
+        Action::ExportRust,
+        Action::PrintAscii,
+        Action::Save,
+        Action::Load,
+        Action::SaveState,
+        Action::LoadState,
+        Action::Screenshot,
+    ];
+
+    fn name(self) -> &'static str {
+        return match self {
+            Action::Quit => "quit",
+            Action::Help => "help",
+            Action::Pause => "pause",
+            Action::Step => "step",
+            Action::Undo => "undo",
+            Action::Finish => "finish",
+            Action::Faster => "faster",
+            Action::Slower => "slower",
+            Action::BiasMode => "bias-mode",
+            Action::Reset => "reset",
+            Action::NextAlgorithm => "next-algorithm",
+            Action::NextTopology => "next-topology",
+            Action::NextPolicy => "next-policy",
+            Action::Compare => "compare",
+            Action::Braid => "braid",
+            Action::Weave => "weave",
+            Action::SolveBfs => "solve-bfs",
+            Action::SolveAStar => "solve-astar",
+            Action::SolveDijkstra => "solve-dijkstra",
+            Action::SolveWallFollower => "solve-wall-follower",
+            Action::SolveDeadEndFilling => "solve-dead-end-filling",
+            Action::ToggleSolution => "toggle-solution",
+            Action::Play => "play",
+            Action::MoveUp => "move-up",
+            Action::MoveRight => "move-right",
+            Action::MoveDown => "move-down",
+            Action::MoveLeft => "move-left",
+            Action::LayerUp => "layer-up",
+            Action::LayerDown => "layer-down",
+            Action::ClearTrail => "clear-trail",
+            Action::Race => "race",
+            Action::AutoSolve => "auto-solve",
+            Action::SecondUp => "second-up",
+            Action::SecondRight => "second-right",
+            Action::SecondDown => "second-down",
+            Action::SecondLeft => "second-left",
+            Action::SecondLayerUp => "second-layer-up",
+            Action::SecondLayerDown => "second-layer-down",
+            Action::Portals => "portals",
+            Action::Edit => "edit",
+            Action::Fill => "fill",
+            Action::ClearFills => "clear-fills",
+            Action::Fog => "fog",
+            Action::FogWider => "fog-wider",
+            Action::FogNarrower => "fog-narrower",
+            Action::Heatmap => "heatmap",
+            Action::Bands => "bands",
+            Action::BandsWider => "bands-wider",
+            Action::BandsNarrower => "bands-narrower",
+            Action::LongestPath => "longest-path",
+            Action::ShowStack => "show-stack",
+            Action::Stats => "stats",
+            Action::NextTheme => "next-theme",
+            Action::ResetView => "reset-view",
+            Action::Follow => "follow",
+            Action::ExportPng => "export-png",
+            Action::ExportSvg => "export-svg",
+            Action::ExportDot => "export-dot",
+            Action::ExportRust => "export-rust",
+            Action::PrintAscii => "print-ascii",
+            Action::Save => "save",
+            Action::Load => "load",
+            Action::SaveState => "save-state",
+            Action::LoadState => "load-state",
+            Action::Screenshot => "screenshot",
+        };
+    }
+
+    /// What the action does, for the help overlay.
+    fn description(self) -> &'static str {
+        return match self {
+            Action::Quit => "quit",
+            Action::Help => "show these keys",
+            Action::Pause => "pause or resume the generator",
+            Action::Step => "take a single step while paused",
+            Action::Undo => "take back a step while paused",
+            Action::Finish => "finish the maze at once",
+            Action::Faster => "speed up, or carve straighter in bias mode",
+            Action::Slower => "slow down, or carve less straight in bias mode",
+            Action::BiasMode => "switch + and - to the straightness",
+            Action::Reset => "start a new maze",
+            Action::NextAlgorithm => "start a new maze with the next algorithm",
+            Action::NextTopology => "start a new maze with the next topology",
+            Action::NextPolicy => "change how the growing tree picks cells",
+            Action::Compare => "generate a second maze next to this one",
+            Action::Braid => "turn dead ends into loops",
+            Action::Weave => "add bridges over straight corridors",
+            Action::SolveBfs => "solve with breadth-first search",
+            Action::SolveAStar => "solve with A*",
+            Action::SolveDijkstra => "solve with Dijkstra's algorithm",
+            Action::SolveWallFollower => "solve by following the wall",
+            Action::SolveDeadEndFilling => "solve by filling dead ends",
+            Action::ToggleSolution => "show or hide the solution",
+            Action::Play => "walk through the maze, or stop",
+            Action::MoveUp => "move the player up",
+            Action::MoveRight => "move the player right",
+            Action::MoveDown => "move the player down",
+            Action::MoveLeft => "move the player left",
+            Action::LayerUp => "go or look up the stairs",
+            Action::LayerDown => "go or look down the stairs",
+            Action::ClearTrail => "clear the trail of the player",
+            Action::Race => "race a second player to the goal",
+            Action::AutoSolve => "walk the shortest way to the goal",
+            Action::SecondUp => "move the second player up in a race",
+            Action::SecondRight => "move the second player right in a race",
+            Action::SecondDown => "move the second player down in a race",
+            Action::SecondLeft => "move the second player left in a race",
+            Action::SecondLayerUp => "take the second player up the stairs",
+            Action::SecondLayerDown => "take the second player down the stairs",
+            Action::Portals => "place portals at random",
+            Action::Edit => "edit walls, left click removes and right click adds",
+            Action::Fill => "color what a clicked cell connects to",
+            Action::ClearFills => "clear the colors of the fill tool",
+            Action::Fog => "hide what the player can't see",
+            Action::FogWider => "let the player see farther",
+            Action::FogNarrower => "let the player see less far",
+            Action::Heatmap => "color the cells by distance",
+            Action::Bands => "color the cells in bands by distance",
+            Action::BandsWider => "make the bands of color wider",
+            Action::BandsNarrower => "make the bands of color narrower",
+            Action::LongestPath => "show the longest path",
+            Action::ShowStack => "show the path of the backtracker",
+            Action::Stats => "show the statistics",
+            Action::NextTheme => "switch to the next theme",
+            Action::ResetView => "reset zoom and pan",
+            Action::Follow => "keep the cell being carved in view",
+            Action::ExportPng => "save maze.png",
+            Action::ExportSvg => "save maze.svg",
+            Action::ExportDot => "save the passages as a graph to maze.dot",
+            Action::ExportRust => "save the walls as a Rust array to maze.rs",
+            Action::PrintAscii => "print the maze as text",
+            Action::Save => "save maze.json",
+            Action::Load => "load maze.json",
+            Action::SaveState => "save maze-state.json",
+            Action::LoadState => "load maze-state.json",
+            Action::Screenshot => "save a screenshot",
+        };
+    }
+
+    fn default_keys(self) -> &'static [KeyCode] {
+        return match self {
+            Action::Quit => &[KeyCode::Q, KeyCode::Escape],
+            Action::Help => &[KeyCode::F1],
+            Action::Pause => &[KeyCode::Space],
+            Action::Step => &[KeyCode::Period],
+            Action::Undo => &[KeyCode::Comma],
+            Action::Finish => &[KeyCode::I],
+            Action::Faster => &[KeyCode::Equal, KeyCode::KpAdd],
+            Action::Slower => &[KeyCode::Minus, KeyCode::KpSubtract],
+            Action::BiasMode => &[KeyCode::N],
+            Action::Reset => &[KeyCode::R],
+            Action::NextAlgorithm => &[KeyCode::A],
+            Action::NextTopology => &[KeyCode::O],
+            Action::NextPolicy => &[KeyCode::G],
+            Action::Compare => &[KeyCode::M],
+            Action::Braid => &[KeyCode::K],
+            Action::Weave => &[KeyCode::E],
+            Action::SolveBfs => &[KeyCode::B],
+            Action::SolveAStar => &[KeyCode::H],
+            Action::SolveDijkstra => &[KeyCode::J],
+            Action::SolveWallFollower => &[KeyCode::W],
+            Action::SolveDeadEndFilling => &[KeyCode::F],
+            Action::ToggleSolution => &[KeyCode::S],
+            Action::Play => &[KeyCode::Enter],
+            Action::MoveUp => &[KeyCode::Up],
+            Action::MoveRight => &[KeyCode::Right],
+            Action::MoveDown => &[KeyCode::Down],
+            Action::MoveLeft => &[KeyCode::Left],
+            Action::LayerUp => &[KeyCode::PageUp],
+            Action::LayerDown => &[KeyCode::PageDown],
+            Action::ClearTrail => &[KeyCode::U],
+            Action::Race => &[KeyCode::F11],
+            Action::AutoSolve => &[KeyCode::Backslash],
+            Action::SecondUp => &[KeyCode::W],
+            Action::SecondRight => &[KeyCode::D],
+            Action::SecondDown => &[KeyCode::S],
+            Action::SecondLeft => &[KeyCode::A],
+            Action::SecondLayerUp => &[KeyCode::R],
+            Action::SecondLayerDown => &[KeyCode::F],
+            Action::Portals => &[KeyCode::Y],
+            Action::Edit => &[KeyCode::F2],
+            Action::Fill => &[KeyCode::F3],
+            Action::ClearFills => &[KeyCode::Backspace],
+            Action::Fog => &[KeyCode::X],
+            Action::FogWider => &[KeyCode::RightBracket],
+            Action::FogNarrower => &[KeyCode::LeftBracket],
+            Action::Heatmap => &[KeyCode::D],
+            Action::Bands => &[KeyCode::F4],
+            Action::BandsWider => &[KeyCode::Apostrophe],
+            Action::BandsNarrower => &[KeyCode::Semicolon],
+            Action::LongestPath => &[KeyCode::L],
+            Action::ShowStack => &[KeyCode::Z],
+            Action::Stats => &[KeyCode::Tab],
+            Action::NextTheme => &[KeyCode::C],
+            Action::ResetView => &[KeyCode::Key0],
+            Action::Follow => &[KeyCode::F7],
+            Action::ExportPng => &[KeyCode::P],
+            Action::ExportSvg => &[KeyCode::V],
+            Action::ExportDot => &[KeyCode::F8],
+            Action::ExportRust => &[KeyCode::Insert],
+            Action::PrintAscii => &[KeyCode::T],
+            Action::Save => &[KeyCode::F5],
+            Action::Load => &[KeyCode::F9],
+            Action::SaveState => &[KeyCode::F6],
+            Action::LoadState => &[KeyCode::F10],
+            Action::Screenshot => &[KeyCode::F12],
+        };
+    }
+
+    /// Whether the action reads or writes a file, which a browser can't.
+    fn uses_files(self) -> bool {
+        return matches!(
+            self,
+            Action::ExportPng
+                | Action::ExportSvg
+                | Action::ExportDot
+                | Action::ExportRust
+                | Action::Save
+                | Action::Load
+                | Action::SaveState
+                | Action::LoadState
+                | Action::Screenshot
+        );
+    }
+}
+
+/// Keys that can be bound to an action, by the name of their `KeyCode`.
+const BINDABLE_KEYS: [KeyCode; 90] = [
+    KeyCode::Space,
+    KeyCode::Apostrophe,
+    KeyCode::Comma,
+    KeyCode::Minus,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Semicolon,
+    KeyCode::Equal,
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::LeftBracket,
+    KeyCode::Backslash,
+    KeyCode::RightBracket,
+    KeyCode::GraveAccent,
+    KeyCode::Escape,
+    KeyCode::Enter,
+    KeyCode::Tab,
+    KeyCode::Backspace,
+    KeyCode::Insert,
+    KeyCode::Delete,
+    KeyCode::Right,
+    KeyCode::Left,
+    KeyCode::Down,
+    KeyCode::Up,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::Kp0,
+    KeyCode::Kp1,
+    KeyCode::Kp2,
+    KeyCode::Kp3,
+    KeyCode::Kp4,
+    KeyCode::Kp5,
+    KeyCode::Kp6,
+    KeyCode::Kp7,
+    KeyCode::Kp8,
+    KeyCode::Kp9,
+    KeyCode::KpDecimal,
+    KeyCode::KpDivide,
+    KeyCode::KpMultiply,
+    KeyCode::KpSubtract,
+    KeyCode::KpAdd,
+    KeyCode::KpEnter,
+];
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    return BINDABLE_KEYS
+        .into_iter()
+        .find(|key| format!("{:?}", key).eq_ignore_ascii_case(name));
+}
+
+/// One key name or a list of them in the `[keys]` table of the config file.
+#[derive(Debug, serde::Deserialize)]
+#[serde(untagged)]
+enum KeyNames {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyNames {
+    fn names(&self) -> Vec<&str> {
+        return match self {
+            KeyNames::One(name) => vec![name.as_str()],
+            KeyNames::Many(names) => names.iter().map(String::as_str).collect(),
+        };
+    }
+}
+
+/// The keys that trigger each action: the `default_keys`, unless the config
+/// file binds others.
+struct KeyBindings {
+    keys: HashMap<Action, Vec<KeyCode>>,
+    /// Actions whose keys do nothing else for now, see `claim`.
+    claimed: Vec<Action>,
+    /// The actions held down on a gamepad this frame and the one before, see
+    /// `hold`.
+    held: Vec<Action>,
+    held_before: Vec<Action>,
+}
+
+impl KeyBindings {
+    /// The default bindings with the ones in `names` in their place, leaving
+    /// out any names that aren't actions or keys.
+    fn new(names: &HashMap<String, KeyNames>) -> Self {
+        let mut keys: HashMap<Action, Vec<KeyCode>> = Action::ALL
+            .into_iter()
+            .map(|action| (action, action.default_keys().to_vec()))
+            .collect();
+        for action in Action::ALL {
+            if let Some(bound) = names.get(action.name()) {
+                keys.insert(
+                    action,
+                    bound.names().into_iter().filter_map(parse_key).collect(),
+                );
+            }
+            // there are no files to save to or load from in a browser
+            if IN_BROWSER && action.uses_files() {
+                keys.insert(action, Vec::new());
+            }
         }
-        if is_key_pressed(KeyCode::R) {
-            grid = Grid::new(rows, cols);
+        return Self {
+            keys,
+            claimed: Vec::new(),
+            held: Vec::new(),
+            held_before: Vec::new(),
+        };
+    }
+
+    /// Counts `actions` as held down this frame, along with their keys.
+    fn hold(&mut self, actions: Vec<Action>) {
+        self.held_before = std::mem::replace(&mut self.held, actions);
+    }
+
+    /// Keeps the keys of `actions` from triggering any other action, until
+    /// they are claimed by others. Nothing is claimed with no `actions`.
+    fn claim(&mut self, actions: Vec<Action>) {
+        self.claimed = actions;
+    }
+
+    /// Whether `key` counts for `action`, or only for the claimed actions.
+    fn counts(&self, action: Action, key: KeyCode) -> bool {
+        return self.claimed.contains(&action)
+            || !self
+                .claimed
+                .iter()
+                .any(|claimed| self.keys[claimed].contains(&key));
+    }
+
+    fn pressed(&self, action: Action) -> bool {
+        let held = self.held.contains(&action) && !self.held_before.contains(&action);
+        return held
+            || self.keys[&action]
+                .iter()
+                .any(|&key| is_key_pressed(key) && self.counts(action, key));
+    }
+
+    fn down(&self, action: Action) -> bool {
+        return self.held.contains(&action)
+            || self.keys[&action]
+                .iter()
+                .any(|&key| is_key_down(key) && self.counts(action, key));
+    }
+
+    /// The names of the keys bound to `action`, or a dash if there are none.
+    fn describe(&self, action: Action) -> String {
+        let keys = &self.keys[&action];
+        if keys.is_empty() {
+            return "-".to_string();
         }
-        if is_key_pressed(KeyCode::Space) {
-            paused ^= true;
+        let names: Vec<String> = keys.iter().map(|key| format!("{:?}", key)).collect();
+        return names.join(", ");
+    }
+}
+
+/// Where the preferences are read from, in the working directory.
+const CONFIG_PATH: &str = "puzzler.toml";
+
+/// Preferences from `CONFIG_PATH`, which take the place of the built-in
+/// defaults. The command line has the last word on the rows and columns.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Config {
+    /// Size of a square cell on screen at the initial zoom.
+    cell_size: Option<f32>,
+    wall_width: Option<f32>,
+    /// The algorithm of the first maze, see `GenAlgorithm::parse`.
+    algorithm: Option<String>,
+    rows: Option<usize>,
+    cols: Option<usize>,
+    /// One of `Theme::NAMES`, to start with instead of the first.
+    theme: Option<String>,
+    /// Colors of the starting theme by the name of their `Theme` field.
+    colors: HashMap<String, String>,
+    /// Keys for each action by its name, see `KeyBindings`.
+    keys: HashMap<String, KeyNames>,
+    /// Whether two arrows held together move the player across a corner of a
+    /// square maze, see `Grid::move_player_diagonally`.
+    diagonal_moves: bool,
+    /// Whether square cells stretch into rectangles to fill the window, see
+    /// `Grid::set_cell_size`.
+    stretch: bool,
+    /// Cells per second the auto-solver walks.
+    auto_solve_speed: Option<f32>,
+}
+
+impl Config {
+    /// The preferences in `path`, or the defaults if there is no such file.
+    fn load(path: &str) -> Result<Self, String> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(format!("could not read {}: {}", path, err)),
+        };
+        let config: Config =
+            toml::from_str(&text).map_err(|err| format!("invalid {}: {}", path, err))?;
+
+        for (name, size) in [
+            ("cell_size", config.cell_size),
+            ("wall_width", config.wall_width),
+            ("auto_solve_speed", config.auto_solve_speed),
+        ] {
+            if size.is_some_and(|size| size.is_nan() || size <= 0.0) {
+                return Err(format!("`{}` in {} has to be above 0", name, path));
+            }
+        }
+        for (name, dimension) in [("rows", config.rows), ("cols", config.cols)] {
+            if dimension == Some(0) {
+                return Err(format!("`{}` in {} has to be at least 1", name, path));
+            }
+        }
+        if let Some(name) = &config.algorithm {
+            GenAlgorithm::parse(name)
+                .ok_or_else(|| format!("unknown algorithm `{}` in {}", name, path))?;
+        }
+        if let Some(name) = &config.theme {
+            if !Theme::NAMES.contains(&name.as_str()) {
+                return Err(format!("unknown theme `{}` in {}", name, path));
+            }
         }
+        let mut theme = Theme::DARK;
+        for (name, color) in config.colors.iter() {
+            theme
+                .color_mut(name)
+                .ok_or_else(|| format!("unknown color `{}` in {}", name, path))?;
+            parse_color(color)
+                .ok_or_else(|| format!("invalid color `{}` for `{}` in {}", color, name, path))?;
+        }
+        for (name, keys) in config.keys.iter() {
+            if !Action::ALL.iter().any(|action| action.name() == name) {
+                return Err(format!("unknown action `{}` in {}", name, path));
+            }
+            if let Some(key) = keys
+                .names()
+                .into_iter()
+                .find(|&key| parse_key(key).is_none())
+            {
+                return Err(format!("unknown key `{}` for `{}` in {}", key, name, path));
+            }
+        }
+        return Ok(config);
+    }
+
+    /// How much bigger cells are drawn than `CELL_SIZE` at the initial zoom.
+    fn scale(&self) -> f32 {
+        return self.cell_size.unwrap_or(CELL_SIZE) / CELL_SIZE;
+    }
+
+    fn wall_width(&self) -> f32 {
+        return self.wall_width.unwrap_or(WALL_WIDTH);
+    }
+
+    fn auto_solve_speed(&self) -> f32 {
+        return self.auto_solve_speed.unwrap_or(AUTO_SOLVE_SPEED);
+    }
+
+    fn algorithm(&self) -> GenAlgorithm {
+        return self
+            .algorithm
+            .as_deref()
+            .and_then(GenAlgorithm::parse)
+            .unwrap_or(GenAlgorithm::RecursiveBacktracker);
+    }
 
-        clear_background(BACKGROUND_COLOR);
+    fn key_bindings(&self) -> KeyBindings {
+        return KeyBindings::new(&self.keys);
+    }
 
-        for cell in grid.cells.iter() {
-            cell.draw();
+    /// The theme presets with the colors changed, and which one to start with.
+    fn themes(&self) -> ([Theme; 4], usize) {
+        let mut themes = Theme::PRESETS;
+        let index = Theme::NAMES
+            .iter()
+            .position(|&name| Some(name) == self.theme.as_deref())
+            .unwrap_or(0);
+        for (name, color) in self.colors.iter() {
+            if let (Some(field), Some(color)) = (themes[index].color_mut(name), parse_color(color))
+            {
+                *field = color;
+            }
         }
-        grid.cells[grid.current].highlight(HIGHLIGHT_COLOR2);
+        return (themes, index);
+    }
+}
 
-        if !paused {
-            grid.update_current();
+const USAGE: &str = "usage: puzzler [--rows <rows>] [--cols <cols>] [--seed <seed>]
+               [--topology <square|hex|triangle|polar>] [--layers <layers>]
+               [--wrap <x|y|xy>] [--rooms <rooms>] [--density <density>]
+               [--terrain <share>] [--coins <coins>] [--mask <image>] [--floor <image>] [--wall <image>]
+               [--headless [--format <png|ascii|dot|edges|rust|maze>] [--output <file>]]
+               [--record gif [--stride <steps>] [--output <file>]]
+               [--batch <count> [--format <format>] [--out <dir>]]
+               [--tui] [--bench <runs>] [--parallel]";
+
+/// Command-line options; anything left out falls back to the window-derived default.
+#[derive(Debug, Default)]
+struct Args {
+    rows: Option<usize>,
+    cols: Option<usize>,
+    seed: Option<u64>,
+    topology: Topology,
+    layers: Option<usize>,
+    /// Whether the maze wraps around horizontally and vertically.
+    wrap: (bool, bool),
+    /// How many open rooms the recursive backtracker carves around.
+    rooms: usize,
+    /// Share of the cells the maze is carved into, all of them if left out.
+    density: Option<f32>,
+    /// Share of the cells that are swamp, which costs more to walk through.
+    terrain: f32,
+    /// How many coins to collect on the way to the goal in play mode.
+    coins: usize,
+    /// Only the dark pixels of this image are carved, a cell for each.
+    mask: Option<Mask>,
+    /// Images to draw visited cells and walls with.
+    floor: Option<String>,
+    wall: Option<String>,
+    /// Generate a maze, write it to a file and exit without opening a window.
+    headless: bool,
+    /// Step through the generation in the terminal instead of a window.
+    tui: bool,
+    format: Format,
+    output: Option<String>,
+    /// Record the generation as an animated GIF and exit without opening a window.
+    record: bool,
+    /// Generator steps between two frames of the recording.
+    stride: Option<usize>,
+    /// Generate this many mazes into `out` and exit without opening a window.
+    batch: Option<usize>,
+    out: Option<String>,
+    /// Time every algorithm this many times and exit without opening a window.
+    bench: Option<usize>,
+    /// Generate parts of the maze on every core when headless or benchmarking.
+    parallel: bool,
+    /// Everything else from `CONFIG_PATH`.
+    config: Config,
+}
+
+/// File format of a maze generated with `--headless`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    #[default]
+    Png,
+    Ascii,
+    /// The passages as a Graphviz graph.
+    Dot,
+    /// The passages as one `a b` line each.
+    Edges,
+    /// The walls as a Rust array.
+    Rust,
+    /// The walls packed into the binary `.maze` format.
+    Maze,
+}
+
+impl Format {
+    fn default_output(self) -> &'static str {
+        return match self {
+            Format::Png => "maze.png",
+            Format::Ascii => "maze.txt",
+            Format::Dot => "maze.dot",
+            Format::Edges => "maze-edges.txt",
+            Format::Rust => "maze.rs",
+            Format::Maze => "maze.maze",
+        };
+    }
+
+    fn extension(self) -> &'static str {
+        return match self {
+            Format::Png => "png",
+            Format::Ascii | Format::Edges => "txt",
+            Format::Dot => "dot",
+            Format::Rust => "rs",
+            Format::Maze => "maze",
+        };
+    }
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Self::default();
+
+        while let Some(arg) = args.next() {
+            if arg == "-h" || arg == "--help" {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            if arg == "--headless" {
+                parsed.headless = true;
+                continue;
+            }
+            if arg == "--tui" {
+                parsed.tui = true;
+                continue;
+            }
+            if arg == "--parallel" {
+                parsed.parallel = true;
+                continue;
+            }
+
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for `{}`", arg))?;
+            match arg.as_str() {
+                "--rows" => parsed.rows = Some(parse_dimension(&arg, &value)?),
+                "--cols" => parsed.cols = Some(parse_dimension(&arg, &value)?),
+                "--layers" => parsed.layers = Some(parse_dimension(&arg, &value)?),
+                "--rooms" => {
+                    parsed.rooms = value
+                        .parse()
+                        .map_err(|_| format!("invalid number of rooms `{}`", value))?;
+                }
+                "--coins" => {
+                    parsed.coins = value
+                        .parse()
+                        .map_err(|_| format!("invalid number of coins `{}`", value))?;
+                }
+                "--density" => {
+                    parsed.density = match value.parse::<f32>() {
+                        Ok(density) if density > 0.0 && density <= 1.0 => Some(density),
+                        _ => {
+                            return Err(format!(
+                                "`--density` has to be above 0 and at most 1, got `{}`",
+                                value
+                            ))
+                        }
+                    };
+                }
+                "--terrain" => {
+                    parsed.terrain = match value.parse::<f32>() {
+                        Ok(terrain) if (0.0..=1.0).contains(&terrain) => terrain,
+                        _ => {
+                            return Err(format!(
+                                "`--terrain` has to be between 0 and 1, got `{}`",
+                                value
+                            ))
+                        }
+                    };
+                }
+                "--seed" => {
+                    let seed = value
+                        .parse()
+                        .map_err(|_| format!("invalid seed `{}`", value))?;
+                    parsed.seed = Some(seed);
+                }
+                "--wrap" => {
+                    parsed.wrap = match value.as_str() {
+                        "x" => (true, false),
+                        "y" => (false, true),
+                        "xy" => (true, true),
+                        _ => return Err(format!("`--wrap` has to be x, y or xy, got `{}`", value)),
+                    };
+                }
+                "--format" => {
+                    parsed.format = match value.as_str() {
+                        "png" => Format::Png,
+                        "ascii" => Format::Ascii,
+                        "dot" => Format::Dot,
+                        "edges" => Format::Edges,
+                        "rust" => Format::Rust,
+                        "maze" => Format::Maze,
+                        _ => {
+                            return Err(format!(
+                            "`--format` has to be png, ascii, dot, edges, rust or maze, got `{}`",
+                            value
+                        ))
+                        }
+                    };
+                }
+                "--output" => parsed.output = Some(value),
+                "--record" => {
+                    if value != "gif" {
+                        return Err(format!("`--record` has to be gif, got `{}`", value));
+                    }
+                    parsed.record = true;
+                }
+                "--stride" => parsed.stride = Some(parse_dimension(&arg, &value)?),
+                "--batch" => parsed.batch = Some(parse_dimension(&arg, &value)?),
+                "--out" => parsed.out = Some(value),
+                "--bench" => parsed.bench = Some(parse_dimension(&arg, &value)?),
+                "--mask" => parsed.mask = Some(Mask::load(&value)?),
+                "--floor" => parsed.floor = Some(value),
+                "--wall" => parsed.wall = Some(value),
+                "--topology" => {
+                    parsed.topology = Topology::parse(&value)
+                        .ok_or_else(|| format!("unknown topology `{}`", value))?;
+                }
+                _ => return Err(format!("unknown argument `{}`", arg)),
+            }
+        }
+        return Ok(parsed);
+    }
+}
+
+fn parse_dimension(arg: &str, value: &str) -> Result<usize, String> {
+    return match value.parse::<usize>() {
+        Ok(dimension) if dimension >= 1 => Ok(dimension),
+        _ => Err(format!("`{}` has to be at least 1, got `{}`", arg, value)),
+    };
+}
+
+fn window_conf() -> Conf {
+    return Conf {
+        window_resizable: true,
+        window_width: 800,
+        window_height: 800,
+        window_title: "Puzzler".to_string(),
+        ..Default::default()
+    };
+}
+
+fn main() {
+    // a browser has neither a command line nor a config file, so it gets
+    // the defaults
+    if IN_BROWSER {
+        macroquad::Window::from_config(window_conf(), run(Args::default()));
+        return;
+    }
+
+    let mut args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    };
+    let config = match Config::load(CONFIG_PATH) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+    };
+    args.rows = args.rows.or(config.rows);
+    args.cols = args.cols.or(config.cols);
+    if let Some(mask) = args.mask.as_ref() {
+        if args.topology != Topology::Square || args.layers.unwrap_or(1) != 1 {
+            eprintln!("error: `--mask` only works for square mazes with a single layer");
+            std::process::exit(1);
+        }
+        (args.rows, args.cols) = (Some(mask.rows()), Some(mask.cols()));
+    }
+    args.config = config;
+
+    if let Some(runs) = args.bench {
+        bench(&args, runs);
+        return;
+    }
+    if let Some(count) = args.batch {
+        if let Err(err) = generate_batch(&args, count) {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.record {
+        if let Err(err) = record_gif(&args) {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.headless {
+        if let Err(err) = generate_headless(&args) {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.tui {
+        if let Err(err) = run_tui(&args) {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    macroquad::Window::from_config(window_conf(), run(args));
+}
+
+/// The maze to generate without a window, sized to fit the default one.
+fn headless_grid(args: &Args, seed: u64) -> Result<Grid, String> {
+    if args.topology != Topology::Square || args.layers.unwrap_or(1) != 1 {
+        return Err("only square mazes with a single layer can be exported".to_string());
+    }
+
+    let conf = window_conf();
+    let (width, height) = (conf.window_width as f32, conf.window_height as f32);
+    let (rows, cols) = grid_size(args, args.topology, width, height);
+    let grid = new_grid(
+        args,
+        (rows, cols),
+        args.topology,
+        args.config.algorithm(),
+        seed,
+    );
+    return Ok(grid);
+}
+
+/// A new maze with the `--layers`, `--wrap`, `--density`, `--rooms` and
+/// `--terrain` of `args`, or carved into its `--mask` if it's square.
+fn new_grid(
+    args: &Args,
+    (rows, cols): (usize, usize),
+    topology: Topology,
+    algorithm: GenAlgorithm,
+    seed: u64,
+) -> Grid {
+    let mut grid = match args.mask.as_ref() {
+        Some(mask) if topology == Topology::Square => Grid::with_mask(mask, algorithm, seed),
+        _ => {
+            let layers = args.layers.unwrap_or(1);
+            let mut grid =
+                Grid::with_layers(rows, cols, layers, topology, args.wrap, algorithm, seed);
+            grid.block_cells(args.density.unwrap_or(1.0));
+            grid
+        }
+    };
+    grid.carve_rooms(args.rooms);
+    if args.terrain > 0.0 {
+        grid.add_terrain(args.terrain);
+    }
+    return grid;
+}
+
+/// Generates the whole maze at once, on as many threads as there are cores
+/// with `--parallel`.
+fn generate_full(grid: &mut Grid, args: &Args) {
+    if args.parallel {
+        let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+        grid.generate_parallel(cores);
+    } else {
+        grid.generate_full();
+    }
+}
+
+/// Generates a whole maze and writes it to the output file, without touching the window.
+fn generate_headless(args: &Args) -> Result<(), String> {
+    let mut grid = headless_grid(args, args.seed.unwrap_or_else(|| fastrand::u64(..)))?;
+    generate_full(&mut grid, args);
+
+    let output = args
+        .output
+        .as_deref()
+        .unwrap_or(args.format.default_output());
+    export(&grid, args.format, output, args.config.wall_width())?;
+    println!("saved {}", output);
+    return Ok(());
+}
+
+/// Generates a maze in the terminal, drawing it again with box-drawing
+/// characters after every command read from stdin: an empty line takes a
+/// step, a number takes that many, `f` finishes the maze and `q` quits.
+fn run_tui(args: &Args) -> Result<(), String> {
+    if args.topology != Topology::Square || args.layers.unwrap_or(1) != 1 {
+        return Err(
+            "only square mazes with a single layer can be drawn in the terminal".to_string(),
+        );
+    }
+
+    let size = (args.rows.unwrap_or(TUI_ROWS), args.cols.unwrap_or(TUI_COLS));
+    let seed = args.seed.unwrap_or_else(|| fastrand::u64(..));
+    let mut grid = new_grid(args, size, Topology::Square, args.config.algorithm(), seed);
+    let mut lines = std::io::stdin().lines();
+    let mut error = None;
+    loop {
+        // clear the terminal and draw from the top left
+        print!("\x1b[2J\x1b[H{}", grid.to_box_drawing()?);
+        if grid.finished {
+            println!("Done, seed {}", grid.seed);
+            return Ok(());
+        }
+        println!(
+            "{:.0}% - Enter: step, <number>: that many steps, f: finish, q: quit",
+            grid.progress() * 100.0
+        );
+        // after drawing, so that it isn't cleared right away
+        if let Some(err) = error.take() {
+            eprintln!("error: {}", err);
+        }
+
+        let Some(line) = lines.next() else {
+            return Ok(());
+        };
+        let line = line.map_err(|err| format!("could not read stdin: {}", err))?;
+        match line.trim() {
+            "" => grid.step(),
+            "f" => generate_full(&mut grid, args),
+            "q" => return Ok(()),
+            command => match command.parse::<usize>() {
+                Ok(steps) => {
+                    for _ in 0..steps {
+                        if grid.finished {
+                            break;
+                        }
+                        grid.step();
+                    }
+                }
+                Err(_) => error = Some(format!("unknown command `{}`", command)),
+            },
+        }
+    }
+}
+
+/// Writes `grid` to `output` in `format`, with walls `wall_width` thick in images.
+fn export(grid: &Grid, format: Format, output: &str, wall_width: f32) -> Result<(), String> {
+    let result = match format {
+        Format::Png => grid
+            .export_png(output, wall_width)
+            .map_err(|err| err.to_string()),
+        Format::Ascii => grid
+            .to_ascii()
+            .and_then(|ascii| std::fs::write(output, ascii).map_err(|err| err.to_string())),
+        Format::Dot => std::fs::write(output, grid.to_dot()).map_err(|err| err.to_string()),
+        Format::Edges => std::fs::write(output, grid.to_edge_list()).map_err(|err| err.to_string()),
+        Format::Rust => {
+            std::fs::write(output, grid.to_rust_source("MAZE")).map_err(|err| err.to_string())
+        }
+        Format::Maze => grid.save_maze(output).map_err(|err| err.to_string()),
+    };
+    return result.map_err(|err| format!("could not save {}: {}", output, err));
+}
+
+/// Generates `count` whole mazes like `generate_headless` and writes each to
+/// `maze_<seed>` in the `--out` directory, with the seeds counting up from
+/// `--seed` or from 0.
+fn generate_batch(args: &Args, count: usize) -> Result<(), String> {
+    let dir = std::path::Path::new(args.out.as_deref().unwrap_or("."));
+    std::fs::create_dir_all(dir)
+        .map_err(|err| format!("could not create {}: {}", dir.display(), err))?;
+    let first = args.seed.unwrap_or(0);
+    for seed in (first..).take(count) {
+        let mut grid = headless_grid(args, seed)?;
+        generate_full(&mut grid, args);
+        let name = format!("maze_{}.{}", seed, args.format.extension());
+        let output = dir.join(name);
+        export(
+            &grid,
+            args.format,
+            &output.to_string_lossy(),
+            args.config.wall_width(),
+        )?;
+        println!(
+            "saved {} (difficulty {:.0})",
+            output.display(),
+            grid.difficulty()
+        );
+    }
+    return Ok(());
+}
+
+/// Generates `runs` whole mazes with every algorithm that supports the grid,
+/// and prints how long they took.
+fn bench(args: &Args, runs: usize) {
+    let conf = window_conf();
+    let (width, height) = (conf.window_width as f32, conf.window_height as f32);
+    let (rows, cols) = grid_size(args, args.topology, width, height);
+    let layers = args.layers.unwrap_or(1);
+    println!(
+        "{} runs of {}x{}x{} {:?} mazes",
+        runs, rows, cols, layers, args.topology
+    );
+    println!(
+        "{:<22} {:>12} {:>12} {:>12}",
+        "algorithm", "mean", "min", "max"
+    );
+
+    let mut algorithm = GenAlgorithm::RecursiveBacktracker;
+    loop {
+        // masks fall back to the recursive backtracker for the others
+        let masked = args.mask.is_some() && args.topology == Topology::Square;
+        if algorithm.supports(args.topology, layers) && (!masked || algorithm.grows()) {
+            let mut times = Vec::new();
+            for run in 0..runs {
+                let seed = match args.seed {
+                    Some(seed) => seed.wrapping_add(run as u64),
+                    None => fastrand::u64(..),
+                };
+                let start = std::time::Instant::now();
+                let mut grid = new_grid(args, (rows, cols), args.topology, algorithm, seed);
+                generate_full(&mut grid, args);
+                times.push(start.elapsed());
+            }
+            let mean = times.iter().sum::<std::time::Duration>() / runs as u32;
+            println!(
+                "{:<22} {:>12.2?} {:>12.2?} {:>12.2?}",
+                algorithm.name(),
+                mean,
+                times.iter().min().unwrap(),
+                times.iter().max().unwrap()
+            );
+        }
+        algorithm = algorithm.cycle();
+        if algorithm == GenAlgorithm::RecursiveBacktracker {
+            break;
+        }
+    }
+}
+
+/// Generates a whole maze like `generate_headless`, but writes a frame of it to
+/// an animated GIF every `--stride` steps. Each frame is encoded right away
+/// instead of being kept until the end, as a large maze takes thousands.
+fn record_gif(args: &Args) -> Result<(), String> {
+    use image::codecs::gif::{GifEncoder, Repeat};
+
+    let mut grid = headless_grid(args, args.seed.unwrap_or_else(|| fastrand::u64(..)))?;
+    let stride = args.stride.unwrap_or(DEFAULT_RECORD_STRIDE);
+    let output = args.output.as_deref().unwrap_or("maze.gif");
+    let error = |err: &dyn std::fmt::Display| format!("could not save {}: {}", output, err);
+
+    let file = std::fs::File::create(output).map_err(|err| error(&err))?;
+    let mut encoder = GifEncoder::new(std::io::BufWriter::new(file));
+    encoder
+        .set_repeat(Repeat::Infinite)
+        .map_err(|err| error(&err))?;
+    let mut frames = 0;
+    loop {
+        let delay = if grid.finished {
+            LAST_FRAME_DELAY_MS
+        } else {
+            FRAME_DELAY_MS
+        };
+        let frame = image::Frame::from_parts(
+            grid.render_image(true, args.config.wall_width()),
+            0,
+            0,
+            image::Delay::from_numer_denom_ms(delay, 1),
+        );
+        encoder.encode_frame(frame).map_err(|err| error(&err))?;
+        frames += 1;
+        if grid.finished {
+            break;
+        }
+        for _ in 0..stride {
+            grid.step();
+        }
+    }
+    println!("saved {} with {} frames", output, frames);
+    return Ok(());
+}
+
+/// The grid dimensions for the current window size, unless given on the command line.
+fn grid_size(args: &Args, topology: Topology, width: f32, height: f32) -> (usize, usize) {
+    let scale = args.config.scale();
+    let (width, height) = (width / scale, height / scale);
+    let (rows, cols) = topology.fit(width, height, args.config.wall_width());
+    return (args.rows.unwrap_or(rows), args.cols.unwrap_or(cols));
+}
+
+/// Zoom and pan state of the maze view, shared by both mazes when comparing
+/// them. Each maze is shown in an area of the window, with the top left
+/// corner of the maze at the top left of the area at first.
+struct View {
+    /// World position shown in the center of the area.
+    target: Vec2,
+    zoom: f32,
+}
+
+impl View {
+    /// The view of `area` with the world drawn `scale` times its size.
+    fn new(area: Rect, scale: f32) -> Self {
+        return Self {
+            target: area.size() / scale / 2.0,
+            zoom: scale,
+        };
+    }
+
+    fn camera(&self, area: Rect) -> Camera2D {
+        return Camera2D {
+            target: self.target,
+            zoom: vec2(self.zoom * 2.0 / area.w, -self.zoom * 2.0 / area.h),
+            // viewports are measured from the bottom of the window
+            viewport: Some((
+                area.x as i32,
+                (screen_height() - area.y - area.h) as i32,
+                area.w as i32,
+                area.h as i32,
+            )),
+            ..Default::default()
+        };
+    }
+
+    /// The world rectangle that is shown in `area`.
+    fn visible_rect(&self, area: Rect) -> Rect {
+        let size = area.size() / self.zoom;
+        let corner = self.target - size / 2.0;
+        return Rect::new(corner.x, corner.y, size.x, size.y);
+    }
+
+    /// The world position under `cursor` in the maze shown in `area`.
+    fn screen_to_world(&self, area: Rect, cursor: Vec2) -> Vec2 {
+        return self.target + (cursor - area.center()) / self.zoom;
+    }
+
+    /// Zooms by `factor` while keeping the world position under `cursor` in place.
+    fn zoom_at(&mut self, area: Rect, cursor: Vec2, factor: f32) {
+        let world = self.screen_to_world(area, cursor);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.target = world - (cursor - area.center()) / self.zoom;
+    }
+
+    /// Moves the view along with a drag of `delta` screen pixels.
+    fn pan(&mut self, delta: Vec2) {
+        self.target -= delta / self.zoom;
+    }
+
+    /// The view of `area` that shows all of `bounds` as large as it fits.
+    fn fitting(area: Rect, bounds: Rect) -> Self {
+        return Self {
+            target: bounds.center(),
+            zoom: (area.w / bounds.w)
+                .min(area.h / bounds.h)
+                .clamp(MIN_ZOOM, MAX_ZOOM),
+        };
+    }
+
+    /// Moves and zooms the share `t` of the way to `goal`.
+    fn ease_to(&mut self, goal: &View, t: f32) {
+        self.target = self.target.lerp(goal.target, t);
+        self.zoom += (goal.zoom - self.zoom) * t;
+    }
+}
+
+/// Makes the square cells of `grid` as wide and high as it takes for the
+/// maze to fill `area` at the initial zoom `scale`.
+fn stretch_cells(grid: &mut Grid, area: Rect, scale: f32) {
+    if grid.topology == Topology::Square {
+        let size = area.size() / scale / vec2(grid.cols as f32, grid.rows as f32);
+        grid.set_cell_size((size.x, size.y));
+    }
+}
+
+/// Which cells of `shown_layer` are at least partly inside the world
+/// rectangle `rect`, so that the others can be skipped when drawing cell by
+/// cell.
+fn cells_in_view(grid: &Grid, rect: Rect) -> Vec<bool> {
+    // how far a cell can reach from its center
+    let reach = match grid.topology {
+        Topology::Square => Vec2::from(grid.cell_size()) / 2.0,
+        Topology::Hex => Vec2::splat(HEX_RADIUS),
+        Topology::Triangle => vec2(TRIANGLE_SIDE / 2.0, TRIANGLE_HEIGHT * 2.0 / 3.0),
+        Topology::Polar => Vec2::splat(1.5 * RING_WIDTH),
+    };
+    let (min, max) = (rect.point() - reach, rect.point() + rect.size() + reach);
+    return (0..grid.cells.len())
+        .map(|index| {
+            let center = grid.center(index);
+            return grid.cells[index].layer == grid.shown_layer
+                && center.cmpge(min).all()
+                && center.cmple(max).all();
+        })
+        .collect();
+}
+
+/// The world rectangle covering every wall of `grid`, `wall_width` thick.
+fn maze_bounds(grid: &Grid, wall_width: f32) -> Rect {
+    let points = (0..grid.cells.len())
+        .flat_map(|index| grid.walls(index))
+        .flat_map(|(from, to)| [from, to]);
+    let (min, max) = points.fold(
+        (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+        |(min, max), point| (min.min(point), max.max(point)),
+    );
+    if min.x > max.x {
+        return Rect::new(0.0, 0.0, CELL_SIZE, CELL_SIZE);
+    }
+    let margin = wall_width;
+    return Rect::new(
+        min.x - margin,
+        min.y - margin,
+        max.x - min.x + 2.0 * margin,
+        max.y - min.y + 2.0 * margin,
+    );
+}
+
+/// The gamepads, read once a frame to play like the keyboard: the d-pad and
+/// the left stick are the arrows, the bumpers take the stairs, `A` starts a
+/// new maze and `Start` starts or stops walking.
+struct Gamepads {
+    #[cfg(not(target_arch = "wasm32"))]
+    context: Option<quad_gamepad::ControllerContext>,
+}
+
+impl Gamepads {
+    fn new() -> Self {
+        // looking for gamepads on Linux panics without an input directory,
+        // like in a container
+        #[cfg(not(target_arch = "wasm32"))]
+        let context = {
+            let input = std::path::Path::new("/dev/input");
+            if cfg!(target_os = "linux") && !input.is_dir() {
+                None
+            } else {
+                quad_gamepad::ControllerContext::new()
+            }
+        };
+        return Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            context,
+        };
+    }
+
+    /// The actions held down on any of the gamepads right now. A stick pushed
+    /// past `STICK_DEADZONE` holds the one arrow it is closest to.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn read(&mut self) -> Vec<Action> {
+        use quad_gamepad::{ControllerStatus, GamepadButton, MAX_DEVICES};
+
+        let Some(context) = self.context.as_mut() else {
+            return Vec::new();
+        };
+        for _ in 0..GAMEPAD_POLLS {
+            context.update();
+        }
+
+        let buttons = [
+            (GamepadButton::DpadUp, Action::MoveUp),
+            (GamepadButton::DpadRight, Action::MoveRight),
+            (GamepadButton::DpadDown, Action::MoveDown),
+            (GamepadButton::DpadLeft, Action::MoveLeft),
+            (GamepadButton::BumperRight, Action::LayerUp),
+            (GamepadButton::BumperLeft, Action::LayerDown),
+            (GamepadButton::A, Action::Reset),
+            (GamepadButton::Start, Action::Play),
+        ];
+        let mut held = Vec::new();
+        for index in 0..MAX_DEVICES {
+            let state = context.state(index);
+            if state.status != ControllerStatus::Connected {
+                continue;
+            }
+            for (button, action) in buttons {
+                if state.digital_state[button as usize] {
+                    held.push(action);
+                }
+            }
+
+            // pushing a stick down is positive like on screen, except on Windows
+            let flip = if cfg!(target_os = "windows") {
+                -1.0
+            } else {
+                1.0
+            };
+            let stick = vec2(state.analog_state[0], state.analog_state[1] * flip);
+            if stick.x.abs().max(stick.y.abs()) < STICK_DEADZONE {
+                continue;
+            }
+            let arrow = if stick.x.abs() > stick.y.abs() {
+                if stick.x > 0.0 {
+                    Action::MoveRight
+                } else {
+                    Action::MoveLeft
+                }
+            } else if stick.y > 0.0 {
+                Action::MoveDown
+            } else {
+                Action::MoveUp
+            };
+            held.push(arrow);
+        }
+        return held;
+    }
+
+    /// Browsers have no gamepads here.
+    #[cfg(target_arch = "wasm32")]
+    fn read(&mut self) -> Vec<Action> {
+        return Vec::new();
+    }
+}
+
+/// The actions that move one of the players.
+struct Controls {
+    /// Up, right, down and left on screen.
+    arrows: [Action; 4],
+    layer_up: Action,
+    layer_down: Action,
+}
+
+impl Controls {
+    const FIRST: Controls = Controls {
+        arrows: [
+            Action::MoveUp,
+            Action::MoveRight,
+            Action::MoveDown,
+            Action::MoveLeft,
+        ],
+        layer_up: Action::LayerUp,
+        layer_down: Action::LayerDown,
+    };
+
+    const SECOND: Controls = Controls {
+        arrows: [
+            Action::SecondUp,
+            Action::SecondRight,
+            Action::SecondDown,
+            Action::SecondLeft,
+        ],
+        layer_up: Action::SecondLayerUp,
+        layer_down: Action::SecondLayerDown,
+    };
+
+    fn actions(&self) -> Vec<Action> {
+        let mut actions = self.arrows.to_vec();
+        actions.extend([self.layer_up, self.layer_down]);
+        return actions;
+    }
+
+    /// Where the keys pressed this frame take a player on the cell at `from`,
+    /// if anywhere.
+    fn step(&self, grid: &Grid, from: usize, keys: &KeyBindings, diagonal: bool) -> Option<usize> {
+        let directions = [
+            vec2(0.0, -1.0),
+            vec2(1.0, 0.0),
+            vec2(0.0, 1.0),
+            vec2(-1.0, 0.0),
+        ];
+        // holding a second arrow picks between diagonals, like on hexagons
+        if self.arrows.iter().any(|&action| keys.pressed(action)) {
+            let direction: Vec2 = self
+                .arrows
+                .iter()
+                .zip(directions)
+                .filter(|&(&action, _)| keys.down(action))
+                .fold(Vec2::ZERO, |sum, (_, direction)| sum + direction);
+            if direction != Vec2::ZERO {
+                let diagonal = diagonal
+                    .then(|| grid.step_diagonally(from, direction))
+                    .flatten();
+                let to = diagonal.or_else(|| grid.step_towards(from, direction));
+                if to.is_some() {
+                    return to;
+                }
+            }
+        }
+        if keys.pressed(self.layer_up) {
+            return grid.climb_from(from, true);
+        }
+        if keys.pressed(self.layer_down) {
+            return grid.climb_from(from, false);
+        }
+        return None;
+    }
+}
+
+/// Marks the cells of the trail of `attempt` in `color`, fading out towards
+/// the oldest ones, and the ones walked back out of in `backtracked`.
+fn draw_trail(grid: &Grid, attempt: &Attempt, color: Color, backtracked: Color, wall_width: f32) {
+    let walked_back = attempt.backtracked();
+    let (oldest, newest) = TRAIL_FADE;
+    // only the last time the player stepped on a cell counts
+    let mut drawn = HashSet::new();
+    for (step, &index) in attempt.trail.iter().enumerate().rev() {
+        if !drawn.insert(index) {
+            continue;
+        }
+        let fade = (step + 1) as f32 / attempt.trail.len() as f32;
+        let color = if walked_back.contains(&index) {
+            backtracked
+        } else {
+            color
+        };
+        let alpha = color.a * (oldest + (newest - oldest) * fade);
+        if grid.cells[index].layer == grid.shown_layer {
+            grid.mark(index, Color { a: alpha, ..color }, wall_width);
+        }
+    }
+}
+
+/// The second player of a race, who walks through the same maze from the
+/// same start as the first one.
+struct Race {
+    player: usize,
+    attempt: Attempt,
+}
+
+/// Time and moves of one walk through the maze in play mode.
+struct Attempt {
+    /// When the first move was made, as returned by `get_time`.
+    started: Option<f64>,
+    moves: u32,
+    /// Seconds from the first move until the goal was reached.
+    solved_in: Option<f64>,
+    /// Every cell the player stepped on, oldest first.
+    trail: Vec<usize>,
+}
+
+impl Attempt {
+    fn new() -> Self {
+        return Self {
+            started: None,
+            moves: 0,
+            solved_in: None,
+            trail: Vec::new(),
+        };
+    }
+
+    /// The cells of the trail the player walked back out of the way they
+    /// came, like from a dead end.
+    fn backtracked(&self) -> HashSet<usize> {
+        let mut path: Vec<usize> = Vec::new();
+        let mut backtracked = HashSet::new();
+        for &cell in self.trail.iter() {
+            if path.len() >= 2 && path[path.len() - 2] == cell {
+                backtracked.extend(path.pop());
+            } else {
+                backtracked.remove(&cell);
+                path.push(cell);
+            }
+        }
+        return backtracked;
+    }
+
+    /// Counts a move from `from` to `to` made at `time`, onto a cell that costs
+    /// `weight` moves, starting the clock with the first one.
+    fn record_move(&mut self, from: usize, to: usize, time: f64, weight: u32) {
+        self.started.get_or_insert(time);
+        self.moves += weight;
+        if self.trail.is_empty() {
+            self.trail.push(from);
+        }
+        self.trail.push(to);
+    }
+
+    /// Seconds since the first move, or until the goal if it was reached.
+    fn elapsed(&self, time: f64) -> f64 {
+        return match (self.solved_in, self.started) {
+            (Some(seconds), _) => seconds,
+            (None, Some(started)) => time - started,
+            (None, None) => 0.0,
+        };
+    }
+}
+
+/// A new maze generated by `algorithm` with the density and rooms given on
+/// the command line, keeping the settings of `grid`.
+fn regenerate(
+    grid: &Grid,
+    rows: usize,
+    cols: usize,
+    topology: Topology,
+    algorithm: GenAlgorithm,
+    args: &Args,
+) -> Grid {
+    let mut regenerated = new_grid(args, (rows, cols), topology, algorithm, fastrand::u64(..));
+    regenerated.policy = grid.policy;
+    regenerated.straightness = grid.straightness;
+    regenerated.shown_layer = grid.shown_layer;
+    return regenerated;
+}
+
+/// The next algorithm after the one of `grid` that can generate its mazes,
+/// and leave its blocked cells alone if it has any.
+fn next_algorithm(grid: &Grid) -> GenAlgorithm {
+    let blocked = grid.carved_len() < grid.cells.len();
+    let mut algorithm = grid.algorithm.cycle();
+    while !algorithm.supports(grid.topology, grid.layers) || blocked && !algorithm.grows() {
+        algorithm = algorithm.cycle();
+    }
+    return algorithm;
+}
+
+/// The same maze as `grid`, down to the seed, but generated by the next
+/// algorithm, to be compared with it.
+fn rival_of(grid: &Grid) -> Grid {
+    let (layers, wrap) = (grid.layers, (grid.wrap_x, grid.wrap_y));
+    let algorithm = next_algorithm(grid);
+    let mut rival = Grid::with_layers(
+        grid.rows,
+        grid.cols,
+        layers,
+        grid.topology,
+        wrap,
+        algorithm,
+        grid.seed,
+    );
+    rival.policy = grid.policy;
+    rival.straightness = grid.straightness;
+    return rival;
+}
+
+/// Whether `rival` was made by `rival_of` for the maze `grid` is generating.
+fn is_rival(rival: &Grid, grid: &Grid) -> bool {
+    return rival.seed == grid.seed
+        && rival.topology == grid.topology
+        && rival.rows == grid.rows
+        && rival.cells.len() == grid.cells.len()
+        && (rival.wrap_x, rival.wrap_y) == (grid.wrap_x, grid.wrap_y);
+}
+
+/// Lists the bindings of every action over the maze, in as many columns as
+/// it takes to fit the window.
+fn draw_help(keys: &KeyBindings, theme: &Theme) {
+    let background = Color {
+        a: 0.85,
+        ..theme.background
+    };
+    draw_rectangle(0.0, 0.0, screen_width(), screen_height(), background);
+    let lines: Vec<String> = Action::ALL
+        .iter()
+        .map(|&action| {
+            format!(
+                "{}: {} ({})",
+                keys.describe(action),
+                action.description(),
+                action.name()
+            )
+        })
+        .collect();
+    let per_column = (((screen_height() - 20.0) / 20.0) as usize).max(1);
+    let mut x = 10.0;
+    for column in lines.chunks(per_column) {
+        let mut width: f32 = 0.0;
+        for (line, text) in column.iter().enumerate() {
+            width = width.max(measure_text(text, None, 20, 1.0).width);
+            draw_text(text, x, 30.0 + line as f32 * 20.0, 20.0, theme.foreground);
+        }
+        x += width + 30.0;
+    }
+}
+
+async fn run(args: Args) {
+    let (mut rows, mut cols) = grid_size(&args, args.topology, screen_width(), screen_height());
+    let skin = Skin::load(&args).await;
+
+    let mut paused = true;
+    let mut steps_per_frame = DEFAULT_STEPS_PER_FRAME;
+    // whether `+` and `-` change the straightness instead of the speed
+    let mut bias_mode = false;
+    let mut grid = new_grid(
+        &args,
+        (rows, cols),
+        args.topology,
+        args.config.algorithm(),
+        args.seed.unwrap_or_else(|| fastrand::u64(..)),
+    );
+    let mut solution_cost: Option<u32> = None;
+    let mut show_solution = true;
+    let window = Rect::new(0.0, 0.0, screen_width(), screen_height());
+    let mut view = View::new(window, args.config.scale());
+    // whether the view keeps up with the generator, and the view it eases
+    // into to show the whole maze once it's done
+    let mut follow = false;
+    let mut fit: Option<View> = None;
+    // a second maze generated alongside the first, in the right half of the window
+    let mut compare = false;
+    let mut rival: Option<Grid> = None;
+    let (themes, mut theme_index) = args.config.themes();
+    let mut last_mouse = Vec2::from(mouse_position());
+    let mut inspected: Option<usize> = None;
+    let mut playing = false;
+    let mut attempt = Attempt::new();
+    let mut race: Option<Race> = None;
+    // seconds since the auto-solver took its last step
+    let mut auto_solve_time = 0.0;
+    // the first end of a portal that was right-clicked
+    let mut pending_portal: Option<usize> = None;
+    // whether clicks change the walls instead
+    let mut editing = false;
+    // whether clicks flood-fill the cells connected to the one clicked, and
+    // the color of every filled cell
+    let mut filling = false;
+    let mut fills: HashMap<usize, Color> = HashMap::new();
+    let mut fog = false;
+    let mut fog_radius = DEFAULT_FOG_RADIUS;
+    let mut heatmap = false;
+    let mut bands = false;
+    let mut band_size = DEFAULT_BAND_SIZE;
+    let mut show_longest = false;
+    let mut show_stack = false;
+    let mut show_stats = false;
+    let mut show_help = false;
+    let mut keys = args.config.key_bindings();
+    let mut gamepads = Gamepads::new();
+    let mut wall_mesh = WallMesh::new(None);
+    let mut overview: Option<Overview> = None;
+    // the layer and theme the walls were built for, and whether as an overview
+    let mut mesh_for = (0, theme_index, false);
+    let mut rival_mesh = WallMesh::new(None);
+    let mut rival_overview: Option<Overview> = None;
+    let mut rival_mesh_for = (0, theme_index, false);
+    // fastest time and fewest moves for each seed, which need not be the same walk
+    let mut best: HashMap<u64, (f64, u32)> = HashMap::new();
+    // whether the grid was still being generated last frame, and when it finished
+    let mut generating = !grid.finished;
+    let mut done_at: Option<f64> = None;
+
+    loop {
+        keys.hold(gamepads.read());
+        if keys.down(Action::Quit) {
+            break;
+        }
+        let toggled = keys.pressed(Action::Compare);
+        compare ^= toggled;
+        let window = Rect::new(0.0, 0.0, screen_width(), screen_height());
+        // when comparing, the grid takes the left half and its rival the right one
+        let area = Rect {
+            w: if compare { window.w / 2.0 } else { window.w },
+            ..window
+        };
+        if toggled {
+            view = View::new(area, args.config.scale());
+        }
+        let rival_area = Rect { x: area.w, ..area };
+        if grid_size(&args, grid.topology, area.w, area.h) != (rows, cols) {
+            (rows, cols) = grid_size(&args, grid.topology, area.w, area.h);
+            grid = regenerate(&grid, rows, cols, grid.topology, grid.algorithm, &args);
+            solution_cost = None;
+            inspected = None;
+            pending_portal = None;
+            fills.clear();
+            view = View::new(area, args.config.scale());
+        }
+
+        let mouse = Vec2::from(mouse_position());
+        let (_, wheel) = mouse_wheel();
+        if wheel != 0.0 {
+            view.zoom_at(area, mouse, if wheel > 0.0 { 1.1 } else { 1.0 / 1.1 });
+            fit = None;
+        }
+        if is_mouse_button_down(MouseButton::Middle) {
+            view.pan(mouse - last_mouse);
+            fit = None;
+        }
+        last_mouse = mouse;
+        if keys.pressed(Action::Follow) {
+            follow ^= true;
+            fit = None;
+        }
+        let ease = (FOLLOW_RATE * get_frame_time()).min(1.0);
+        if follow && !grid.finished {
+            view.target = view.target.lerp(grid.center(grid.current), ease);
+            fit = None;
+        }
+        if let Some(goal) = fit.as_ref() {
+            view.ease_to(goal, ease);
+            if (view.zoom - goal.zoom).abs() < 0.001 && view.target.distance(goal.target) < 0.1 {
+                fit = None;
+            }
+        }
+        if keys.pressed(Action::Edit) {
+            editing ^= true;
+            filling = false;
+            inspected = None;
+            pending_portal = None;
+        }
+        if keys.pressed(Action::Fill) {
+            filling ^= true;
+            editing = false;
+            inspected = None;
+            pending_portal = None;
+        }
+        if keys.pressed(Action::ClearFills) {
+            fills.clear();
+        }
+        if filling && is_mouse_button_pressed(MouseButton::Left) && area.contains(mouse) {
+            let clicked = grid.cell_at(view.screen_to_world(area, mouse));
+            if let Some(index) = clicked.filter(|index| !fills.contains_key(index)) {
+                let color = Color {
+                    a: FILL_ALPHA,
+                    ..macroquad::color::hsl_to_rgb(fastrand::f32(), 0.8, 0.5)
+                };
+                for (cell, distance) in grid.distances_from(index).into_iter().enumerate() {
+                    if distance != UNREACHABLE {
+                        fills.insert(cell, color);
+                    }
+                }
+            }
+        }
+        if editing && grid.finished && area.contains(mouse) {
+            // left click opens a wall and right click closes it
+            for (button, closed) in [(MouseButton::Left, false), (MouseButton::Right, true)] {
+                if !is_mouse_button_pressed(button) {
+                    continue;
+                }
+                if let Some((a, b)) = grid.wall_at(view.screen_to_world(area, mouse)) {
+                    match grid.edit_wall(a, b, closed) {
+                        Ok(()) => solution_cost = None,
+                        Err(err) => eprintln!("error: {}", err),
+                    }
+                }
+            }
+        }
+        if is_mouse_button_pressed(MouseButton::Left)
+            && !editing
+            && !filling
+            && area.contains(mouse)
+        {
+            inspected = grid.cell_at(view.screen_to_world(area, mouse));
+            if let Some(index) = inspected {
+                println!("{:?}", grid.cells[index]);
+            }
+        }
+        if keys.pressed(Action::NextTheme) {
+            theme_index = (theme_index + 1) % themes.len();
+        }
+        let theme = themes[theme_index];
+        let racing = keys.pressed(Action::Race) && race.is_none();
+        if (keys.pressed(Action::Play) || keys.pressed(Action::Race)) && grid.finished {
+            if (!playing || racing) && !grid.is_solvable() {
+                eprintln!("error: there is no way from the start to the goal");
+            } else {
+                playing = !playing || racing;
+                attempt = Attempt::new();
+                for cell in grid.cells.iter_mut() {
+                    cell.seen = false;
+                }
+                grid.player = grid.start;
+                grid.shown_layer = grid.cells[grid.player].layer;
+                grid.scatter_coins(args.coins);
+                race = None;
+                if racing {
+                    race = Some(Race {
+                        player: grid.start,
+                        attempt: Attempt::new(),
+                    });
+                }
+            }
+        }
+        if keys.pressed(Action::AutoSolve) && grid.finished {
+            if grid.auto_solver().is_some() {
+                grid.auto_route.clear();
+            } else if !grid.auto_solve_start() {
+                eprintln!("error: there is no way from the start to the goal");
+            }
+            auto_solve_time = 0.0;
+        }
+        if grid.auto_solver().is_some() {
+            let step = 1.0 / args.config.auto_solve_speed();
+            auto_solve_time += get_frame_time();
+            while auto_solve_time >= step && grid.auto_solve_step() {
+                auto_solve_time -= step;
+            }
+        }
+        if keys.pressed(Action::Fog) {
+            fog ^= true;
+        }
+        if keys.pressed(Action::Heatmap) {
+            heatmap ^= true;
+            bands = false;
+        }
+        if keys.pressed(Action::Bands) {
+            bands ^= true;
+            heatmap = false;
+        }
+        if keys.pressed(Action::BandsWider) {
+            band_size += 1;
+        }
+        if keys.pressed(Action::BandsNarrower) {
+            band_size = band_size.saturating_sub(1).max(1);
+        }
+        if keys.pressed(Action::LongestPath) {
+            show_longest ^= true;
+        }
+        if keys.pressed(Action::ShowStack) {
+            show_stack ^= true;
+        }
+        if keys.pressed(Action::Stats) {
+            show_stats ^= true;
+        }
+        if keys.pressed(Action::Help) {
+            show_help ^= true;
+        }
+        if keys.pressed(Action::FogWider) {
+            fog_radius += 1;
+        }
+        if keys.pressed(Action::FogNarrower) {
+            fog_radius = fog_radius.saturating_sub(1);
+        }
+        if !grid.finished {
+            // a new maze is on its way, and the player will start over on it
+            attempt = Attempt::new();
+        }
+        if !playing || !grid.finished {
+            race = None;
+        }
+        // the WASD keys only move the second player while racing
+        let claimed = if race.is_some() {
+            Controls::SECOND.actions()
+        } else {
+            Vec::new()
+        };
+        keys.claim(claimed);
+        // a race is over as soon as either player reaches the goal
+        let won = attempt.solved_in.is_some()
+            || race
+                .as_ref()
+                .is_some_and(|race| race.attempt.solved_in.is_some());
+        if playing && grid.finished && !won {
+            let from = grid.player;
+            let diagonal = args.config.diagonal_moves;
+            let moved = Controls::FIRST.step(&grid, from, &keys, diagonal);
+            if let Some(to) = moved {
+                grid.player = to;
+                attempt.record_move(from, to, get_time(), grid.weight(to));
+                grid.collect_coin(to);
+            }
+            grid.shown_layer = grid.cells[grid.player].layer;
+            if keys.pressed(Action::ClearTrail) {
+                attempt.trail = vec![grid.player];
+            }
+            // the goal only counts once every coin is collected
+            if moved.is_some() && grid.player == grid.goal && grid.coins.is_empty() {
+                let seconds = attempt.elapsed(get_time());
+                attempt.solved_in = Some(seconds);
+                // times with coins depend on where they happened to land
+                if race.is_none() && grid.coins_collected == 0 {
+                    let record = best.entry(grid.seed).or_insert((seconds, attempt.moves));
+                    *record = (record.0.min(seconds), record.1.min(attempt.moves));
+                }
+            }
+
+            if let Some(race) = race.as_mut().filter(|_| attempt.solved_in.is_none()) {
+                let from = race.player;
+                if let Some(to) = Controls::SECOND.step(&grid, from, &keys, diagonal) {
+                    race.player = to;
+                    race.attempt
+                        .record_move(from, to, get_time(), grid.weight(to));
+                    grid.collect_coin(to);
+                    if to == grid.goal && grid.coins.is_empty() {
+                        race.attempt.solved_in = Some(race.attempt.elapsed(get_time()));
+                    }
+                }
+                if keys.pressed(Action::ClearTrail) {
+                    race.attempt.trail = vec![race.player];
+                }
+            }
+        } else {
+            if keys.pressed(Action::LayerUp) && grid.shown_layer + 1 < grid.layers {
+                grid.shown_layer += 1;
+            }
+            if keys.pressed(Action::LayerDown) && grid.shown_layer > 0 {
+                grid.shown_layer -= 1;
+            }
+        }
+        let fogged = fog && playing && grid.finished;
+        let mut visible = vec![false; grid.cells.len()];
+        if fogged {
+            for index in grid.cells_within(grid.player, fog_radius) {
+                visible[index] = true;
+                grid.cells[index].seen = true;
+            }
+        }
+        if keys.pressed(Action::ResetView) {
+            view = View::new(area, args.config.scale());
+            fit = None;
+        }
+        if keys.pressed(Action::Reset) {
+            grid = regenerate(&grid, rows, cols, grid.topology, grid.algorithm, &args);
+            solution_cost = None;
+            inspected = None;
+            pending_portal = None;
+            fills.clear();
+        }
+        if keys.pressed(Action::NextAlgorithm) {
+            grid = regenerate(
+                &grid,
+                rows,
+                cols,
+                grid.topology,
+                next_algorithm(&grid),
+                &args,
+            );
+            solution_cost = None;
+            inspected = None;
+            pending_portal = None;
+            fills.clear();
+        }
+        if keys.pressed(Action::NextTopology) {
+            let topology = grid.topology.cycle();
+            (rows, cols) = grid_size(&args, topology, area.w, area.h);
+            grid = regenerate(&grid, rows, cols, topology, grid.algorithm, &args);
+            solution_cost = None;
+            inspected = None;
+            pending_portal = None;
+            fills.clear();
+            view = View::new(area, args.config.scale());
+        }
+        if keys.pressed(Action::Pause) {
+            paused ^= true;
+        }
+        if keys.pressed(Action::BiasMode) {
+            bias_mode ^= true;
+        }
+        if keys.pressed(Action::Faster) {
+            if bias_mode {
+                grid.straightness = (grid.straightness + STRAIGHTNESS_STEP).min(1.0);
+            } else {
+                steps_per_frame *= 2;
+            }
+        }
+        if keys.pressed(Action::Slower) {
+            if bias_mode {
+                grid.straightness = (grid.straightness - STRAIGHTNESS_STEP).max(0.0);
+            } else {
+                steps_per_frame = (steps_per_frame / 2).max(1);
+            }
+        }
+        let exportable = grid.topology == Topology::Square && grid.layers == 1;
+        let exports = [
+            Action::ExportPng,
+            Action::ExportSvg,
+            Action::ExportRust,
+            Action::PrintAscii,
+        ];
+        if !exportable && exports.iter().any(|&action| keys.pressed(action)) {
+            eprintln!("error: only square mazes with a single layer can be exported");
+        }
+        if keys.pressed(Action::ExportPng) && exportable {
+            match grid.export_png("maze.png", skin.wall_width) {
+                Ok(()) => println!("saved maze.png"),
+                Err(err) => eprintln!("error: could not save maze.png: {}", err),
+            }
+        }
+        if keys.pressed(Action::ExportSvg) && exportable {
+            match grid.export_svg("maze.svg", skin.wall_width) {
+                Ok(()) => println!("saved maze.svg"),
+                Err(err) => eprintln!("error: could not save maze.svg: {}", err),
+            }
+        }
+        if keys.pressed(Action::ExportRust) && exportable {
+            match std::fs::write("maze.rs", grid.to_rust_source("MAZE")) {
+                Ok(()) => println!("saved maze.rs"),
+                Err(err) => eprintln!("error: could not save maze.rs: {}", err),
+            }
+        }
+        // the graph doesn't depend on the shape of the cells
+        if keys.pressed(Action::ExportDot) {
+            match std::fs::write("maze.dot", grid.to_dot()) {
+                Ok(()) => println!("saved maze.dot"),
+                Err(err) => eprintln!("error: could not save maze.dot: {}", err),
+            }
+        }
+        if keys.pressed(Action::PrintAscii) && exportable {
+            match grid.to_ascii() {
+                Ok(ascii) => print!("{}", ascii),
+                Err(err) => eprintln!("error: {}", err),
+            }
+        }
+        if keys.pressed(Action::Save) {
+            match grid.save_json("maze.json") {
+                Ok(()) => println!("saved maze.json"),
+                Err(err) => eprintln!("error: could not save maze.json: {}", err),
+            }
+        }
+        if keys.pressed(Action::Load) {
+            match Grid::load_json("maze.json") {
+                Ok(loaded) => {
+                    grid = loaded;
+                    solution_cost = None;
+                    inspected = None;
+                    pending_portal = None;
+                    fills.clear();
+                    attempt = Attempt::new();
+                }
+                Err(err) => eprintln!("error: could not load maze.json: {}", err),
+            }
+        }
+        if keys.pressed(Action::SaveState) {
+            match grid.save_state("maze-state.json") {
+                Ok(()) => println!("saved maze-state.json"),
+                Err(err) => eprintln!("error: could not save maze-state.json: {}", err),
+            }
+        }
+        if keys.pressed(Action::LoadState) {
+            match Grid::load_state("maze-state.json") {
+                Ok(loaded) => {
+                    grid = loaded;
+                    solution_cost = None;
+                    inspected = None;
+                    pending_portal = None;
+                    fills.clear();
+                    attempt = Attempt::new();
+                }
+                Err(err) => eprintln!("error: could not load maze-state.json: {}", err),
+            }
+        }
+        if keys.pressed(Action::Finish) {
+            grid.generate_full();
+            if let Some(rival) = rival.as_mut() {
+                rival.generate_full();
+            }
+        }
+        if keys.pressed(Action::Portals) && grid.finished {
+            grid.portals.clear();
+            grid.add_portals(PORTAL_PAIRS);
+            solution_cost = None;
+        }
+        if is_mouse_button_pressed(MouseButton::Right)
+            && grid.finished
+            && !editing
+            && area.contains(mouse)
+        {
+            let clicked = grid.cell_at(view.screen_to_world(area, mouse));
+            match (pending_portal, clicked) {
+                (Some(end), Some(other)) => {
+                    if let Err(err) = grid.add_portal(end, other) {
+                        eprintln!("error: {}", err);
+                    }
+                    solution_cost = None;
+                    pending_portal = None;
+                }
+                (None, clicked) => pending_portal = clicked,
+                (Some(_), None) => pending_portal = None,
+            }
+        }
+        if keys.pressed(Action::Braid) && grid.finished {
+            grid.braid(BRAID_FRACTION);
+            solution_cost = None;
+        }
+        if keys.pressed(Action::Weave) && grid.finished {
+            grid.weave(WEAVE_FRACTION);
+            solution_cost = None;
+        }
+        if keys.pressed(Action::ToggleSolution) {
+            show_solution ^= true;
+        }
+        if keys.pressed(Action::NextPolicy) {
+            grid.policy = grid.policy.cycle();
+        }
+        if keys.pressed(Action::SolveBfs) && grid.finished {
+            grid.solve(SolveAlgorithm::Bfs);
+            solution_cost = None;
+        }
+        if keys.pressed(Action::SolveAStar) && grid.finished {
+            grid.solve(SolveAlgorithm::AStar);
+            solution_cost = None;
+        }
+        if keys.pressed(Action::SolveDijkstra) && grid.finished {
+            solution_cost = grid.solve_dijkstra().map(|(cost, _)| cost);
+        }
+        if keys.pressed(Action::SolveWallFollower) && grid.finished {
+            grid.solve(SolveAlgorithm::WallFollower);
+            solution_cost = None;
+        }
+        if keys.pressed(Action::SolveDeadEndFilling) && grid.finished {
+            grid.solve(SolveAlgorithm::DeadEndFilling);
+            solution_cost = None;
+        }
+
+        if !compare {
+            rival = None;
+        } else if !rival.as_ref().is_some_and(|rival| is_rival(rival, &grid)) {
+            rival = Some(rival_of(&grid));
+        }
+        if let Some(rival) = rival.as_mut() {
+            rival.policy = grid.policy;
+            rival.straightness = grid.straightness;
+            rival.shown_layer = grid.shown_layer;
+        }
+        if args.config.stretch {
+            stretch_cells(&mut grid, area, args.config.scale());
+            if let Some(rival) = rival.as_mut() {
+                stretch_cells(rival, rival_area, args.config.scale());
+            }
+        }
+
+        clear_background(theme.background);
+        set_camera(&view.camera(area));
+
+        let zoomed_out = shows_overview(&grid, &view);
+        if grid.walls_changed || mesh_for != (grid.shown_layer, theme_index, zoomed_out) {
+            if zoomed_out {
+                overview = Some(grid.build_overview(theme.foreground, skin.wall_width));
+            } else {
+                wall_mesh = grid.build_wall_mesh(theme.foreground, &skin);
+            }
+            mesh_for = (grid.shown_layer, theme_index, zoomed_out);
+            grid.walls_changed = false;
+        }
+        let on_screen = cells_in_view(&grid, view.visible_rect(area));
+        for index in (0..grid.cells.len()).filter(|&index| on_screen[index]) {
+            grid.fill_cell(index, &theme, &skin);
+        }
+        match overview.as_ref().filter(|_| zoomed_out) {
+            Some(overview) => overview.draw(),
+            None => wall_mesh.draw(),
+        }
+        if heatmap {
+            let distances = grid.costs_from(grid.start);
+            let farthest = distances
+                .iter()
+                .filter(|&&distance| distance != UNREACHABLE)
+                .max()
+                .copied()
+                .unwrap_or(0)
+                .max(1);
+            for (index, &distance) in distances.iter().enumerate() {
+                if !on_screen[index] {
+                    continue;
+                }
+                let color = if distance == UNREACHABLE {
+                    theme.unreachable
+                } else {
+                    lerp_color(theme.near, theme.far, distance as f32 / farthest as f32)
+                };
+                grid.mark(index, color, skin.wall_width);
+            }
+        }
+        if bands {
+            let distances = grid.distances_from(grid.start);
+            for (index, &distance) in distances.iter().enumerate() {
+                if !on_screen[index] {
+                    continue;
+                }
+                let color = if distance == UNREACHABLE {
+                    theme.unreachable
+                } else {
+                    BAND_COLORS[(distance / band_size) as usize % BAND_COLORS.len()]
+                };
+                grid.mark(index, color, skin.wall_width);
+            }
+        }
+        for index in (0..grid.cells.len()).filter(|&index| on_screen[index]) {
+            if grid.weight(index) > 1 {
+                grid.mark(index, theme.swamp, skin.wall_width);
+            }
+        }
+        for (&index, &color) in fills.iter() {
+            grid.mark(index, color, skin.wall_width);
+        }
+        for (index, cell) in grid.cells.iter().enumerate() {
+            if !on_screen[index] {
+                continue;
+            }
+            if !cell.walls.contains(Walls::UP) || !cell.walls.contains(Walls::DOWN) {
+                grid.mark(index, theme.stairs, skin.wall_width);
+            }
+        }
+        if grid.wrap_x || grid.wrap_y {
+            for index in (0..grid.cells.len()).filter(|&index| on_screen[index]) {
+                let crossing = grid
+                    .open_neighbors(index)
+                    .into_iter()
+                    .any(|neighbor| grid.crosses_seam(index, neighbor));
+                if crossing {
+                    grid.mark(index, theme.wrap, skin.wall_width);
+                }
+            }
+        }
+        for &index in grid.came_from.keys() {
+            grid.mark(index, theme.searched, skin.wall_width);
+        }
+        for &index in grid.queue.iter() {
+            grid.mark(index, theme.frontier, skin.wall_width);
+        }
+        for (index, _) in grid.closed.iter().enumerate().filter(|(_, &closed)| closed) {
+            grid.mark(index, theme.closed, skin.wall_width);
+        }
+        for Reverse((_, index)) in grid.open.iter() {
+            grid.mark(*index, theme.frontier, skin.wall_width);
+        }
+        for &index in grid.trail.iter() {
+            grid.mark(index, theme.trail, skin.wall_width);
+        }
+        if grid.solver == Some(SolveAlgorithm::WallFollower) {
+            grid.mark(grid.follower, theme.current, skin.wall_width);
+        }
+        grid.draw_generation(&theme, show_stack);
+        if let Some(index) = inspected {
+            grid.mark(index, theme.frontier, skin.wall_width);
+        }
+        let portals = grid.portals.iter().flat_map(|&(a, b)| [a, b]);
+        let ends = portals.enumerate().map(|(end, index)| (index, end / 2));
+        let pending = pending_portal.map(|index| (index, grid.portals.len()));
+        for (index, pair) in ends.chain(pending) {
+            if grid.cells[index].layer == grid.shown_layer {
+                let center = grid.center(index);
+                let color = PORTAL_COLORS[pair % PORTAL_COLORS.len()];
+                draw_circle_lines(center.x, center.y, PLAYER_RADIUS, PORTAL_WIDTH, color);
+            }
+        }
+        if playing {
+            draw_trail(&grid, &attempt, theme.player, theme.erased, skin.wall_width);
+        }
+        if playing {
+            for &coin in grid.coins.iter() {
+                if grid.cells[coin].layer == grid.shown_layer {
+                    let center = grid.center(coin);
+                    draw_circle(center.x, center.y, COIN_RADIUS, theme.coin);
+                }
+            }
+        }
+        if let Some(race) = race.as_ref() {
+            draw_trail(
+                &grid,
+                &race.attempt,
+                theme.second_player,
+                theme.erased,
+                skin.wall_width,
+            );
+            if grid.cells[race.player].layer == grid.shown_layer {
+                let center = grid.center(race.player);
+                draw_circle(center.x, center.y, PLAYER_RADIUS, theme.second_player);
+            }
+        }
+        if playing && grid.cells[grid.player].layer == grid.shown_layer {
+            let center = grid.center(grid.player);
+            // smaller on top of the second player, so both can be seen
+            let together = race.as_ref().is_some_and(|race| race.player == grid.player);
+            let radius = if together {
+                PLAYER_RADIUS / 2.0
+            } else {
+                PLAYER_RADIUS
+            };
+            draw_circle(center.x, center.y, radius, theme.player);
+        }
+
+        if let (true, Some(solution)) = (show_solution, &grid.solution) {
+            grid.draw_path(solution, theme.solution);
+        }
+        if let Some(index) = grid.auto_solver() {
+            grid.draw_path(&grid.auto_route[..=grid.auto_position], theme.solution);
+            if grid.cells[index].layer == grid.shown_layer {
+                let center = grid.center(index);
+                draw_circle(center.x, center.y, PLAYER_RADIUS, theme.solution);
+            }
+        }
+        if show_longest {
+            grid.draw_path(&grid.longest_path(), theme.longest);
+        }
+        if fogged {
+            let explored = Color {
+                a: EXPLORED_FOG,
+                ..theme.background
+            };
+            for (index, cell) in grid.cells.iter().enumerate() {
+                if on_screen[index] && !visible[index] {
+                    grid.highlight(
+                        index,
+                        if cell.seen {
+                            explored
+                        } else {
+                            theme.background
+                        },
+                    );
+                }
+            }
+        }
+
+        if let Some(rival) = rival.as_mut() {
+            set_camera(&view.camera(rival_area));
+            let zoomed_out = shows_overview(rival, &view);
+            let built_for = (rival.shown_layer, theme_index, zoomed_out);
+            if rival.walls_changed || rival_mesh_for != built_for {
+                if zoomed_out {
+                    rival_overview = Some(rival.build_overview(theme.foreground, skin.wall_width));
+                } else {
+                    rival_mesh = rival.build_wall_mesh(theme.foreground, &skin);
+                }
+                rival_mesh_for = built_for;
+                rival.walls_changed = false;
+            }
+            let on_screen = cells_in_view(rival, view.visible_rect(rival_area));
+            for index in (0..rival.cells.len()).filter(|&index| on_screen[index]) {
+                rival.fill_cell(index, &theme, &skin);
+            }
+            match rival_overview.as_ref().filter(|_| zoomed_out) {
+                Some(overview) => overview.draw(),
+                None => rival_mesh.draw(),
+            }
+            rival.draw_generation(&theme, show_stack);
+        }
+
+        set_default_camera();
+
+        if let Some(rival) = &rival {
+            draw_line(area.w, 0.0, area.w, area.h, WALL_WIDTH, theme.foreground);
+            draw_text(
+                rival.algorithm.name(),
+                rival_area.x + 10.0,
+                20.0,
+                20.0,
+                theme.foreground,
+            );
+        }
+        draw_text(
+            &format!(
+                "{} ({}, seed {})",
+                grid.algorithm.name(),
+                grid.topology.name(),
+                grid.seed
+            ),
+            10.0,
+            20.0,
+            20.0,
+            theme.foreground,
+        );
+        if grid.solve_failed {
+            draw_text(
+                "No solution found",
+                10.0,
+                screen_height() - 30.0,
+                20.0,
+                theme.foreground,
+            );
+        }
+        if let Some(cost) = solution_cost {
+            draw_text(
+                &format!("Cost: {}", cost),
+                10.0,
+                screen_height() - 10.0,
+                20.0,
+                theme.foreground,
+            );
+        }
+        if let Some(cell) = inspected.map(|index| &grid.cells[index]) {
+            draw_text(
+                &format!(
+                    "({}, {}) visited: {}, top: {}, bot: {}, left: {}, right: {}",
+                    cell.row,
+                    cell.col,
+                    cell.visited,
+                    cell.walls.contains(Walls::TOP),
+                    cell.walls.contains(Walls::BOTTOM),
+                    cell.walls.contains(Walls::LEFT),
+                    cell.walls.contains(Walls::RIGHT)
+                ),
+                10.0,
+                screen_height() - 50.0,
+                20.0,
+                theme.foreground,
+            );
+        }
+        let speed = if bias_mode {
+            format!("Straightness: {:.1}", grid.straightness)
+        } else {
+            format!("Steps/frame: {}", steps_per_frame)
+        };
+        draw_text(&speed, 10.0, 40.0, 20.0, theme.foreground);
+        if bands {
+            let text = format!("Band size: {}", band_size);
+            draw_text(&text, 10.0, 80.0, 20.0, theme.foreground);
+        }
+        if grid.algorithm == GenAlgorithm::GrowingTree {
+            draw_text(
+                &format!("Policy: {}", grid.policy.name()),
+                10.0,
+                60.0,
+                20.0,
+                theme.foreground,
+            );
+        }
+
+        if playing && grid.finished {
+            let seconds = attempt.elapsed(get_time());
+            let mut lines = vec![match attempt.solved_in {
+                Some(_) => format!("Solved in {:.1}s / {} moves", seconds, attempt.moves),
+                None => format!("Time: {:.1}s, moves: {}", seconds, attempt.moves),
+            }];
+            if let Some(race) = race.as_ref() {
+                let moves = (attempt.moves, race.attempt.moves);
+                let winner = match (attempt.solved_in, race.attempt.solved_in) {
+                    (Some(seconds), _) => Some((1, seconds)),
+                    (_, Some(seconds)) => Some((2, seconds)),
+                    _ => None,
+                };
+                lines = vec![match winner {
+                    Some((player, seconds)) => format!("Player {} wins in {:.1}s", player, seconds),
+                    None => format!("Race: {} moves against {}", moves.0, moves.1),
+                }];
+            }
+            let coins = grid.coins.len() + grid.coins_collected;
+            if coins > 0 {
+                lines.push(format!("Coins left: {} of {}", grid.coins.len(), coins));
+            }
+            if let Some((seconds, moves)) = best.get(&grid.seed) {
+                lines.push(format!("Best: {:.1}s / {} moves", seconds, moves));
+            }
+            if fog {
+                lines.push(format!("Fog radius: {}", fog_radius));
+            }
+            for (line, text) in lines.iter().enumerate() {
+                let width = measure_text(text, None, 20, 1.0).width;
+                draw_text(
+                    text,
+                    (screen_width() - width) / 2.0,
+                    20.0 + line as f32 * 20.0,
+                    20.0,
+                    theme.foreground,
+                );
+            }
+        }
+
+        if !grid.finished {
+            let width = screen_width() * grid.progress();
+            let y = screen_height() - PROGRESS_BAR_HEIGHT;
+            draw_rectangle(0.0, y, width, PROGRESS_BAR_HEIGHT, theme.foreground);
+        } else if generating {
+            done_at = Some(get_time());
+            // the player starts over on the new maze, with new coins
+            if playing {
+                grid.scatter_coins(args.coins);
+            }
+            if follow {
+                fit = Some(View::fitting(area, maze_bounds(&grid, skin.wall_width)));
+            }
+        }
+        generating = !grid.finished;
+        if let Some(time) = done_at.filter(|&time| get_time() - time < DONE_FLASH_SECONDS) {
+            let fade = 1.0 - ((get_time() - time) / DONE_FLASH_SECONDS) as f32;
+            let color = Color {
+                a: theme.foreground.a * fade,
+                ..theme.foreground
+            };
+            let width = measure_text("Done", None, 40, 1.0).width;
+            draw_text(
+                "Done",
+                (screen_width() - width) / 2.0,
+                screen_height() - 20.0,
+                40.0,
+                color,
+            );
+        }
+
+        if grid.layers > 1 {
+            let text = format!("Layer {}/{}", grid.shown_layer + 1, grid.layers);
+            let width = measure_text(&text, None, 20, 1.0).width;
+            draw_text(
+                &text,
+                screen_width() - width - 10.0,
+                40.0,
+                20.0,
+                theme.foreground,
+            );
+        }
+        if show_stats {
+            let stats = grid.stats();
+            let percent = |count: usize| -> f32 {
+                return count as f32 * 100.0 / stats.cells as f32;
+            };
+            let mut lines = vec![
+                format!("Cells: {}", stats.cells),
+                format!("Dead ends: {}", stats.dead_ends),
+                format!("Junctions: {}", stats.junctions),
+                format!("Straights: {}", stats.straights),
+                format!("Visited: {:.1}%", percent(stats.visited)),
+            ];
+            let drawn = on_screen.iter().filter(|&&shown| shown).count();
+            lines.push(format!(
+                "Drawn: {} cells, {} skipped",
+                drawn,
+                grid.layer_len() - drawn
+            ));
+            if grid.finished {
+                lines.push(format!("Difficulty: {:.0}", grid.difficulty()));
+                lines.push(format!("Fingerprint: {:016x}", grid.fingerprint()));
+            }
+            for (line, text) in lines.iter().enumerate() {
+                let width = measure_text(text, None, 20, 1.0).width;
+                draw_text(
+                    text,
+                    screen_width() - width - 10.0,
+                    60.0 + line as f32 * 20.0,
+                    20.0,
+                    theme.foreground,
+                );
+            }
+        }
+
+        if !paused {
+            for _ in 0..steps_per_frame {
+                grid.step();
+                if let Some(rival) = rival.as_mut() {
+                    rival.step();
+                }
+            }
+            for _ in 0..SOLVER_STEPS_PER_FRAME {
+                grid.solve_step();
+            }
+        } else {
+            let text = "PAUSED";
+            let width = measure_text(text, None, 20, 1.0).width;
+            draw_text(
+                text,
+                screen_width() - width - 10.0,
+                20.0,
+                20.0,
+                theme.foreground,
+            );
+
+            if keys.pressed(Action::Step) {
+                grid.step();
+                grid.solve_step();
+                if let Some(rival) = rival.as_mut() {
+                    rival.step();
+                }
+            }
+            if keys.pressed(Action::Undo) {
+                if let Some(rival) = rival.as_mut() {
+                    rival.undo();
+                }
+                if grid.undo() {
+                    solution_cost = None;
+                }
+            }
+        }
+
+        let tool = match (editing, filling) {
+            (true, _) => Some("EDIT"),
+            (_, true) => Some("FILL"),
+            _ => None,
+        };
+        if let Some(text) = tool {
+            let width = measure_text(text, None, 20, 1.0).width;
+            draw_text(
+                text,
+                screen_width() - width - 10.0,
+                screen_height() - 10.0,
+                20.0,
+                theme.goal,
+            );
+        }
+
+        if show_help {
+            draw_help(&keys, &theme);
+        }
+
+        // last, so everything drawn this frame is in it
+        if keys.pressed(Action::Screenshot) {
+            match save_screenshot() {
+                Ok(path) => println!("saved {}", path),
+                Err(err) => eprintln!("error: could not save the screenshot: {}", err),
+            }
         }
 
         next_frame().await;